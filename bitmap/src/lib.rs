@@ -0,0 +1,314 @@
+type Error = Box<dyn std::error::Error + Send + Sync>;
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+// A monochrome picture, row after row.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Bitmap {
+	pub w: usize,
+	pub h: usize,
+	pub pixels: Vec<bool>,
+}
+
+impl Bitmap {
+	// Reads a plain (P1) or raw (P4) PBM.
+	pub fn from_pbm(data: &[u8]) -> Result<Bitmap> {
+		let mut pos = 0;
+		let mut token = || -> Result<&[u8]> {
+			loop {
+				match data.get(pos) {
+					Some(b'#') => while data.get(pos).is_some_and(|&c| c != b'\n') { pos += 1 },
+					Some(c) if c.is_ascii_whitespace() => pos += 1,
+					Some(_) => break,
+					None => return Err("truncated PBM header".into()),
+				}
+			}
+			let start = pos;
+			while data.get(pos).is_some_and(|c| !c.is_ascii_whitespace()) {
+				pos += 1;
+			}
+			Ok(&data[start..pos])
+		};
+
+		let magic = token()?.to_vec();
+		let w: usize = std::str::from_utf8(token()?)?.parse()?;
+		let h: usize = std::str::from_utf8(token()?)?.parse()?;
+		if w == 0 || h == 0 {
+			return Err("PBM has no pixels".into());
+		}
+		let size = w.checked_mul(h).ok_or("PBM is too large")?;
+
+		let pixels = match &magic[..] {
+			b"P1" => {
+				let digits = data[pos..].iter().filter(|c| !c.is_ascii_whitespace());
+				digits.take(size).map(|&c| match c {
+					b'0' => Ok(false),
+					b'1' => Ok(true),
+					_ => Err(format!("{:?} is not a PBM pixel", c as char)),
+				}).collect::<Result<Vec<_>, _>>()?
+			},
+			b"P4" => {
+				// A single whitespace byte ends the header.
+				let raw = data.get(pos + 1..).unwrap_or(&[]);
+				let stride = w.div_ceil(8);
+				if stride.checked_mul(h).is_none_or(|n| raw.len() < n) {
+					return Err("truncated PBM data".into());
+				}
+				(0..size).map(|i| raw[i / w * stride + i % w / 8] & (0x80 >> (i % w % 8)) != 0).collect()
+			},
+			_ => return Err("not a PBM".into()),
+		};
+		if pixels.len() != size {
+			return Err("truncated PBM data".into());
+		}
+
+		Ok(Bitmap { w, h, pixels })
+	}
+
+	pub fn get(&self, x: usize, y: usize) -> bool {
+		self.pixels[y * self.w + x]
+	}
+
+	pub fn scaled(&self, k: usize) -> Bitmap {
+		let (w, h) = (self.w * k, self.h * k);
+		let mut pixels = Vec::with_capacity(w * h);
+		for y in 0..h {
+			for x in 0..w {
+				pixels.push(self.get(x / k, y / k));
+			}
+		}
+		Bitmap { w, h, pixels }
+	}
+
+	pub fn dump(&self) {
+		for row in self.pixels.chunks_exact(self.w) {
+			for &x in row {
+				print!("{}", if x { '█' } else { ' ' });
+			}
+			println!();
+		}
+	}
+
+	// Plain PBM, lit pixels are black.
+	pub fn to_pbm(&self) -> String {
+		let mut s = format!("P1\n{} {}\n", self.w, self.h);
+		for row in self.pixels.chunks_exact(self.w) {
+			let row: Vec<&str> = row.iter().map(|&x| if x { "1" } else { "0" }).collect();
+			s.push_str(&row.join(" "));
+			s.push('\n');
+		}
+		s
+	}
+
+	// 8-bit grayscale PNG with the image data in stored deflate blocks,
+	// so no compressor is needed.
+	pub fn to_png(&self) -> Vec<u8> {
+		let mut raw = Vec::with_capacity((self.w + 1) * self.h);
+		for row in self.pixels.chunks_exact(self.w) {
+			raw.push(0);
+			raw.extend(row.iter().map(|&x| if x { 0 } else { 255 }));
+		}
+
+		let mut zlib = vec![0x78, 0x01];
+		let mut blocks = raw.chunks(0xffff).peekable();
+		if blocks.peek().is_none() {
+			zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+		}
+		while let Some(block) = blocks.next() {
+			let last = blocks.peek().is_none();
+			let len = block.len() as u16;
+			zlib.push(last as u8);
+			zlib.extend_from_slice(&len.to_le_bytes());
+			zlib.extend_from_slice(&(!len).to_le_bytes());
+			zlib.extend_from_slice(block);
+		}
+		zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+		let mut ihdr = Vec::new();
+		ihdr.extend_from_slice(&(self.w as u32).to_be_bytes());
+		ihdr.extend_from_slice(&(self.h as u32).to_be_bytes());
+		ihdr.extend_from_slice(&[8, 0, 0, 0, 0]);
+
+		let mut png = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+		png_chunk(&mut png, b"IHDR", &ihdr);
+		png_chunk(&mut png, b"IDAT", &zlib);
+		png_chunk(&mut png, b"IEND", &[]);
+		png
+	}
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+	png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+	let start = png.len();
+	png.extend_from_slice(kind);
+	png.extend_from_slice(data);
+	let crc = crc32(&png[start..]);
+	png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+	let mut crc = !0u32;
+	for &b in data {
+		crc ^= b as u32;
+		for _ in 0..8 {
+			crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+		}
+	}
+	!crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+	let (mut a, mut b) = (1u32, 0u32);
+	for &x in data {
+		a = (a + x as u32) % 65521;
+		b = (b + a) % 65521;
+	}
+	(b << 16) | a
+}
+
+const GLYPH_H: usize = 6;
+
+// The capital letters Advent of Code draws, six rows of each one
+// trimmed to its lit columns. Most are 4 wide, I is 3 and Y is 5.
+const FONT: [(char, &str); 18] = [
+	('A', ".##.#..##..######..##..#"),
+	('B', "###.#..####.#..##..####."),
+	('C', ".##.#..##...#...#..#.##."),
+	('E', "#####...###.#...#...####"),
+	('F', "#####...###.#...#...#..."),
+	('G', ".##.#..##...#.###..#.###"),
+	('H', "#..##..######..##..##..#"),
+	('I', "###.#..#..#..#.###"),
+	('J', "..##...#...#...##..#.##."),
+	('K', "#..##.#.##..#.#.#.#.#..#"),
+	('L', "#...#...#...#...#...####"),
+	('O', ".##.#..##..##..##..#.##."),
+	('P', "###.#..##..####.#...#..."),
+	('R', "###.#..##..####.#.#.#..#"),
+	('S', ".####...#....##....####."),
+	('U', "#..##..##..##..##..#.##."),
+	('Y', "#...##...#.#.#...#....#....#.."),
+	('Z', "####...#..#..#..#...####"),
+];
+
+// Reads the letters off a bitmap. They are told apart by the blank
+// columns between them; where one runs into the next, the widest
+// letter that matches is taken.
+pub fn ocr(bitmap: &Bitmap) -> Result<String> {
+	let lit_col = |x: usize| x < bitmap.w && (0..bitmap.h).any(|y| bitmap.get(x, y));
+	let lit_row = |y: usize| (0..bitmap.w).any(|x| bitmap.get(x, y));
+	let left = (0..bitmap.w).find(|&x| lit_col(x)).ok_or("nothing to read")?;
+	let right = (0..bitmap.w).rev().find(|&x| lit_col(x)).unwrap();
+	let top = (0..bitmap.h).find(|&y| lit_row(y)).unwrap();
+	let bottom = (0..bitmap.h).rev().find(|&y| lit_row(y)).unwrap();
+	if bottom - top + 1 != GLYPH_H {
+		return Err(format!("text is {} pixels high, expected {}", bottom - top + 1, GLYPH_H).into());
+	}
+
+	let width = |g: &str| g.len() / GLYPH_H;
+	let matches = |x0: usize, g: &str| {
+		let w = width(g);
+		(top..=bottom).flat_map(|y| (x0..x0 + w).map(move |x| (x, y)))
+			.zip(g.bytes())
+			.all(|((x, y), c)| (x < bitmap.w && bitmap.get(x, y)) == (c == b'#'))
+	};
+
+	let mut text = String::new();
+	let mut x = left;
+	while x <= right {
+		if !lit_col(x) {
+			x += 1;
+			continue;
+		}
+
+		let fits: Vec<_> = FONT.iter().filter(|(_, g)| matches(x, g)).collect();
+		let &&(c, g) = fits.iter()
+			.find(|(_, g)| !lit_col(x + width(g)))
+			.or_else(|| fits.iter().max_by_key(|(_, g)| width(g)))
+			.ok_or_else(|| format!("unknown glyph at column {}", x))?;
+		text.push(c);
+		x += width(g);
+	}
+
+	Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn from_rows(rows: &[&str]) -> Bitmap {
+		Bitmap {
+			w: rows[0].len(),
+			h: rows.len(),
+			pixels: rows.iter().flat_map(|r| r.bytes().map(|c| c == b'#')).collect(),
+		}
+	}
+
+	#[test]
+	fn png() {
+		let b = from_rows(&["#..", ".##"]);
+		let png = b.to_png();
+		assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+		assert_eq!(&png[12..16], b"IHDR");
+		assert_eq!(&png[16..24], &[0, 0, 0, 3, 0, 0, 0, 2]);
+		assert_eq!(&png[png.len() - 12..], &[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]);
+
+		let idat = &png[41..png.len() - 16];
+		assert_eq!(&idat[..7], &[0x78, 0x01, 1, 8, 0, 0xf7, 0xff]);
+		assert_eq!(&idat[7..15], &[0, 0, 255, 255, 0, 255, 0, 0]);
+		assert_eq!(adler32(&idat[7..15]).to_be_bytes(), idat[15..]);
+
+		assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+		assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+	}
+
+	#[test]
+	fn pbm() {
+		let bitmap = from_rows(&["#..#.", ".##..", "....#"]);
+		assert_eq!(bitmap.to_pbm(), "P1\n5 3\n1 0 0 1 0\n0 1 1 0 0\n0 0 0 0 1\n");
+		assert_eq!(Bitmap::from_pbm(bitmap.to_pbm().as_bytes()).unwrap(), bitmap);
+		assert_eq!(Bitmap::from_pbm(b"P1 # comment\n5 3 10010 01100 00001").unwrap(), bitmap);
+		assert_eq!(Bitmap::from_pbm(b"P4\n5 3\n\x90\x60\x08").unwrap(), bitmap);
+
+		assert!(Bitmap::from_pbm(b"P4\n5 3\n\x90\x60").is_err());
+		assert!(Bitmap::from_pbm(b"P1 5 3 1001").is_err());
+		assert!(Bitmap::from_pbm(b"P1 2 1 12").is_err());
+		assert!(Bitmap::from_pbm(b"P3 2 1 255").is_err());
+		assert!(Bitmap::from_pbm(b"P1 99999999999 99999999999\n0 1").is_err());
+		assert!(Bitmap::from_pbm(b"P1\n0 0\n").is_err());
+		assert!(Bitmap::from_pbm(b"P1\n0 3\n").is_err());
+		assert!(Bitmap::from_pbm(b"P4\n5 0\n").is_err());
+	}
+
+	#[test]
+	fn reading() {
+		// Apart, and I does not start on a lit column.
+		let hi = from_rows(&[
+			"..#..#.###",
+			"..#..#..#.",
+			"..####..#.",
+			"..#..#..#.",
+			"..#..#..#.",
+			"..#..#.###",
+		]);
+		assert_eq!(ocr(&hi).unwrap(), "HI");
+
+		// Y takes up all five columns and runs into the Z.
+		let yz = from_rows(&[
+			"#...#####.",
+			"#...#...#.",
+			".#.#...#..",
+			"..#...#...",
+			"..#..#....",
+			"..#..####.",
+		]);
+		assert_eq!(ocr(&yz).unwrap(), "YZ");
+
+		let blank = from_rows(&["....", "...."]);
+		assert_eq!(ocr(&blank).unwrap_err().to_string(), "nothing to read");
+		let short = from_rows(&["#..#", "####", "#..#"]);
+		assert_eq!(ocr(&short).unwrap_err().to_string(), "text is 3 pixels high, expected 6");
+		let bar = from_rows(&["#.##"; 6]);
+		assert_eq!(ocr(&bar).unwrap_err().to_string(), "unknown glyph at column 0");
+	}
+}
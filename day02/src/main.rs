@@ -1,4 +1,7 @@
 use std::io::{self, Read};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::RangeInclusive;
 
 type Error = Box<dyn std::error::Error>;
 type Result<T, E = Error> = std::result::Result<T, E>;
@@ -16,20 +19,17 @@ fn main() -> Result<()> {
 
 	println!("first -> {}", ram[0]);
 
-	for noun in 0..100 {
-		for verb in 0..100 {
-			ram.copy_from_slice(&initial);
+	let poly = execute_symbolic(&initial)?;
+	println!("ram[0] = {}", poly);
 
-			ram[1] = noun;
-			ram[2] = verb;
-
-			execute(&mut ram);
-
-			if ram[0] == 19690720 {
-				println!("second -> {}", 100 * noun + verb);
-				return Ok(())
-			}
-		}
+	let solutions = solve(&poly, 19690720, 0..=99)?;
+	match solutions.as_slice() {
+		[(noun, verb)] => println!("second -> {} (unique)", 100 * noun + verb),
+		[] => println!("second -> no solution"),
+		many => {
+			let answers: Vec<_> = many.iter().map(|(noun, verb)| 100 * noun + verb).collect();
+			println!("second -> {:?} ({} solutions)", answers, many.len());
+		},
 	}
 	
 	Ok(())
@@ -70,6 +70,240 @@ fn execute(ram: &mut [usize]) {
 	}
 }
 
+// A polynomial in noun and verb, keyed by their powers.
+#[derive(PartialEq, Eq, Clone, Debug)]
+struct Poly(BTreeMap<(u32, u32), i64>);
+
+impl Poly {
+	fn constant(x: i64) -> Poly {
+		Poly::term(x, 0, 0)
+	}
+
+	fn term(c: i64, noun: u32, verb: u32) -> Poly {
+		let mut terms = BTreeMap::new();
+		if c != 0 {
+			terms.insert((noun, verb), c);
+		}
+		Poly(terms)
+	}
+
+	fn coefficient(&self, noun: u32, verb: u32) -> i64 {
+		self.0.get(&(noun, verb)).cloned().unwrap_or(0)
+	}
+
+	fn add(&self, other: &Poly) -> Result<Poly> {
+		let mut terms = self.0.clone();
+		for (&k, &c) in other.0.iter() {
+			let sum = terms.get(&k).cloned().unwrap_or(0).checked_add(c).ok_or("coefficient overflow")?;
+			if sum == 0 {
+				terms.remove(&k);
+			} else {
+				terms.insert(k, sum);
+			}
+		}
+		Ok(Poly(terms))
+	}
+
+	fn mul(&self, other: &Poly) -> Result<Poly> {
+		let mut product = Poly(BTreeMap::new());
+		for (&(an, av), &a) in self.0.iter() {
+			for (&(bn, bv), &b) in other.0.iter() {
+				let c = a.checked_mul(b).ok_or("coefficient overflow")?;
+				product = product.add(&Poly::term(c, an + bn, av + bv))?;
+			}
+		}
+		Ok(product)
+	}
+
+	fn is_linear(&self) -> bool {
+		self.0.keys().all(|&(n, v)| n + v <= 1)
+	}
+
+	fn eval(&self, noun: i64, verb: i64) -> Option<i64> {
+		self.0.iter().try_fold(0i64, |acc, (&(n, v), &c)| {
+			let x = c.checked_mul(noun.checked_pow(n)?)?.checked_mul(verb.checked_pow(v)?)?;
+			acc.checked_add(x)
+		})
+	}
+}
+
+impl fmt::Display for Poly {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		if self.0.is_empty() {
+			return write!(f, "0");
+		}
+		for (i, (&(n, v), &c)) in self.0.iter().enumerate() {
+			let mut factors = Vec::new();
+			if c.abs() != 1 || (n, v) == (0, 0) {
+				factors.push(c.abs().to_string());
+			}
+			for &(name, power) in [("noun", n), ("verb", v)].iter() {
+				match power {
+					0 => {},
+					1 => factors.push(name.to_string()),
+					_ => factors.push(format!("{}^{}", name, power)),
+				}
+			}
+			let sign = match (i, c < 0) {
+				(0, true) => "-",
+				(0, false) => "",
+				(_, true) => " - ",
+				(_, false) => " + ",
+			};
+			write!(f, "{}{}", sign, factors.join("*"))?;
+		}
+		Ok(())
+	}
+}
+
+// A cell of the symbolic memory. Reading through an address which
+// depends on noun or verb gives an opaque value: fine as long as it
+// never reaches anything that matters.
+#[derive(PartialEq, Eq, Clone, Debug)]
+enum Value {
+	Known(Poly),
+	Opaque,
+}
+
+impl Value {
+	fn constant(&self) -> Option<i64> {
+		match self {
+			Value::Known(p) if p.0.keys().all(|&k| k == (0, 0)) => Some(p.coefficient(0, 0)),
+			_ => None,
+		}
+	}
+
+	fn address(&self, pc: usize, len: usize) -> Result<Option<usize>> {
+		match self.constant() {
+			Some(a) if a < 0 || a as usize >= len => Err(format!("address {} out of range at {}", a, pc).into()),
+			Some(a) => Ok(Some(a as usize)),
+			None => Ok(None),
+		}
+	}
+}
+
+// Runs the add/mul dialect with noun and verb left as variables,
+// giving ram[0] as a polynomial in them.
+fn execute_symbolic(rom: &[usize]) -> Result<Poly> {
+	let mut ram: Vec<Value> = rom.iter().map(|&x| Value::Known(Poly::constant(x as i64))).collect();
+	if ram.len() < 3 {
+		return Err("no room for noun and verb".into());
+	}
+	ram[1] = Value::Known(Poly::term(1, 1, 0));
+	ram[2] = Value::Known(Poly::term(1, 0, 1));
+
+	let mut pc: usize = 0;
+	loop {
+		let concrete = |i: usize| -> Result<usize> {
+			let cell = ram.get(i).ok_or_else(|| format!("ran off the end at {}", pc))?;
+			cell.address(pc, ram.len())?.ok_or_else(|| format!("{} depends on noun or verb", i).into())
+		};
+
+		let opcode = ram.get(pc).ok_or_else(|| format!("ran off the end at {}", pc))?.constant();
+		match opcode.ok_or_else(|| format!("opcode at {} depends on noun or verb", pc))? {
+			op @ 1 | op @ 2 => {
+				let load = |i: usize| -> Result<Value> {
+					match ram.get(i).ok_or("truncated instruction")?.address(pc, ram.len())? {
+						Some(a) => Ok(ram[a].clone()),
+						None => Ok(Value::Opaque),
+					}
+				};
+				let (a, b) = (load(pc + 1)?, load(pc + 2)?);
+				let ci = concrete(pc + 3).map_err(|e| format!("write through symbolic address: {}", e))?;
+				ram[ci] = match (a, b) {
+					(Value::Known(a), Value::Known(b)) if op == 1 => Value::Known(a.add(&b)?),
+					(Value::Known(a), Value::Known(b)) => Value::Known(a.mul(&b)?),
+					_ => Value::Opaque,
+				};
+				pc += 4;
+			},
+			99 => break,
+			_ => return Err(format!("unknown opcode at {}", pc).into()),
+		}
+	}
+
+	match ram.swap_remove(0) {
+		Value::Known(p) => Ok(p),
+		Value::Opaque => Err("ram[0] depends on a symbolic address".into()),
+	}
+}
+
+// All (noun, verb) in `domain` for which `poly` gives `target`. Being
+// exhaustive over the domain, a single answer is a uniqueness proof.
+fn solve(poly: &Poly, target: i64, domain: RangeInclusive<i64>) -> Result<Vec<(i64, i64)>> {
+	let (lo, hi) = (*domain.start(), *domain.end());
+	let mut solutions = Vec::new();
+
+	if poly.is_linear() {
+		// a * noun + b * verb = r
+		let (a, b) = (poly.coefficient(1, 0), poly.coefficient(0, 1));
+		let r = target - poly.coefficient(0, 0);
+
+		match (a, b) {
+			(0, 0) => if r == 0 {
+				for noun in domain.clone() {
+					solutions.extend(domain.clone().map(|verb| (noun, verb)));
+				}
+			},
+			(0, _) => if r % b == 0 && domain.contains(&(r / b)) {
+				solutions.extend(domain.clone().map(|noun| (noun, r / b)));
+			},
+			(_, 0) => if r % a == 0 && domain.contains(&(r / a)) {
+				solutions.extend(domain.clone().map(|verb| (r / a, verb)));
+			},
+			_ => {
+				let (g, x, y) = extended_gcd(a, b);
+				if r % g == 0 {
+					// noun = n0 + k * step_n, verb = v0 - k * step_v
+					let (n0, v0) = (x * (r / g), y * (r / g));
+					let (step_n, step_v) = (b / g, a / g);
+					let (k1, k2) = steps_within(n0, step_n, lo, hi);
+					let (k3, k4) = steps_within(v0, -step_v, lo, hi);
+					for k in k1.max(k3)..=k2.min(k4) {
+						solutions.push((n0 + k * step_n, v0 - k * step_v));
+					}
+				}
+			},
+		}
+	} else {
+		for noun in domain.clone() {
+			for verb in domain.clone() {
+				if poly.eval(noun, verb) == Some(target) {
+					solutions.push((noun, verb));
+				}
+			}
+		}
+	}
+
+	solutions.sort();
+	Ok(solutions)
+}
+
+// (g, x, y) with a * x + b * y = g = gcd(a, b), g positive.
+fn extended_gcd(a: i64, b: i64) -> (i64, i64, i64) {
+	if b == 0 {
+		if a < 0 { (-a, -1, 0) } else { (a, 1, 0) }
+	} else {
+		let (g, x, y) = extended_gcd(b, a % b);
+		(g, y, x - (a / b) * y)
+	}
+}
+
+// The range of k keeping start + k * step within lo..=hi, step nonzero.
+fn steps_within(start: i64, step: i64, lo: i64, hi: i64) -> (i64, i64) {
+	let (a, b) = (div_ceil(lo - start, step), div_floor(hi - start, step));
+	if step > 0 { (a, b) } else { (div_ceil(hi - start, step), div_floor(lo - start, step)) }
+}
+
+fn div_floor(a: i64, b: i64) -> i64 {
+	let q = a / b;
+	if a % b != 0 && (a < 0) != (b < 0) { q - 1 } else { q }
+}
+
+fn div_ceil(a: i64, b: i64) -> i64 {
+	-div_floor(-a, b)
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -114,4 +348,43 @@ mod tests {
 		execute(&mut ram);
 		assert_eq!(ram, [30, 1, 1, 4, 2, 5, 6, 0, 99]);
 	}
+
+	#[test]
+	fn symbolic() {
+		// The shape of the puzzle: the first instruction reads through
+		// noun and verb, then gets overwritten.
+		let rom = parse(&String::from("1,0,0,3,1,1,2,3,2,3,17,0,1,0,12,0,99,7,0,0")).unwrap();
+
+		let poly = execute_symbolic(&rom).unwrap();
+		assert_eq!(poly.to_string(), "1 + 7*verb + 7*noun");
+		for &(noun, verb) in [(0, 0), (3, 9), (12, 2)].iter() {
+			let mut ram = rom.clone();
+			ram[1] = noun;
+			ram[2] = verb;
+			execute(&mut ram);
+			assert_eq!(poly.eval(noun as i64, verb as i64), Some(ram[0] as i64));
+		}
+
+		// 7 * (noun + verb) + 1 = 71 has a solution for each noun <= 10.
+		let solutions = solve(&poly, 71, 0..=99).unwrap();
+		assert_eq!(solutions.len(), 11);
+		assert!(solutions.iter().all(|&(n, v)| n + v == 10));
+		assert_eq!(solve(&poly, 72, 0..=99).unwrap(), []);
+	}
+
+	#[test]
+	fn solving() {
+		// 460800 * noun + verb + 250635, as in the puzzle.
+		let poly = Poly::term(460800, 1, 0).add(&Poly::term(1, 0, 1)).unwrap().add(&Poly::constant(250635)).unwrap();
+		assert_eq!(poly.to_string(), "250635 + verb + 460800*noun");
+		assert_eq!(solve(&poly, 19690720, 0..=99).unwrap(), []);
+		assert_eq!(solve(&poly, 250635 + 460800 * 42 + 13, 0..=99).unwrap(), [(42, 13)]);
+
+		let square = Poly::term(1, 2, 0).add(&Poly::term(-1, 0, 1)).unwrap();
+		assert_eq!(square.to_string(), "-verb + noun^2");
+		assert_eq!(solve(&square, 0, 0..=99).unwrap(), [(0, 0), (1, 1), (2, 4), (3, 9), (4, 16), (5, 25), (6, 36), (7, 49), (8, 64), (9, 81)]);
+
+		assert!(execute_symbolic(&[1, 0, 0, 0, 99]).is_err());
+		assert!(execute_symbolic(&[1, 5, 6, 1, 1, 1, 2, 0, 99, 0, 0]).is_err());
+	}
 }
@@ -1,65 +1,271 @@
-use std::io::{self};
+use std::io::{self, BufRead};
 use std::str::FromStr;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Result<T, E = Error> = std::result::Result<T, E>;
 
 fn main() -> Result<()> {
-	let mut path1 = String::new();
-	io::stdin().read_line(&mut path1)?;
-	let mut path2 = String::new();
-	io::stdin().read_line(&mut path2)?;
+	let paths = io::stdin().lock().lines().collect::<Result<Vec<_>, _>>()?;
+	let paths: Vec<&str> = paths.iter().map(|p| p.as_str()).filter(|p| !p.trim().is_empty()).collect();
 
-	let (d, s) = solve(&path1, &path2)?;
+	let (d, s) = solve_many(&paths)?;
 	println!("p1 = {}", d);
 	println!("p2 = {}", s);
+
+	// `svg <file>` draws the wires too.
+	let args: Vec<String> = env::args().skip(1).collect();
+	if let [cmd, file] = &args[..] {
+		if cmd != "svg" {
+			return Err(format!("unknown command {}", cmd).into());
+		}
+		let wires = paths.iter().enumerate()
+			.map(|(i, p)| segments(p, i))
+			.collect::<Result<Vec<_>>>()?;
+		fs::write(file, render_svg(&wires))?;
+	}
 	
 	Ok(())
 }
 
-fn solve(path1: &str, path2: &str) -> Result<(i32, i32)> {
-	let w1 = trace(&path1)?;
-	let w2 = trace(&path2)?;
+// Distance to the closest crossing and fewest steps to any, for any
+// number of wires, without visiting every point along them. Crossings
+// are between pairs of different wires.
+fn solve_many(paths: &[&str]) -> Result<(i32, i32)> {
+	if paths.len() < 2 {
+		return Err(format!("need at least two wires, got {}", paths.len()).into());
+	}
+	let wires = paths.iter().enumerate()
+		.map(|(i, p)| segments(p, i))
+		.collect::<Result<Vec<_>>>()?;
 
-	let mut distance = std::i32::MAX;
-	let mut steps = std::i32::MAX;
-	
-	for (p, s1) in &w1 {
-		if let Some(s2) = w2.get(p) {
-			let d = p.0.abs() + p.1.abs();
-			distance = distance.min(d);
+	let found = crossings(&wires);
+	let distance = found.iter().map(|c| c.point.0.abs() + c.point.1.abs()).min();
+	let steps = found.iter().map(|c| c.steps.0 + c.steps.1).min();
+	match (distance, steps) {
+		(Some(d), Some(s)) => Ok((d, s)),
+		_ => Err("the wires never cross".into()),
+	}
+}
 
-			steps = steps.min(s1 + s2);
-		}
+// A straight run of a wire, covering the points after `from` up to
+// `to`; `steps` is how far along the wire `from` is.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+struct Segment {
+	wire: usize,
+	from: (i32, i32),
+	to: (i32, i32),
+	steps: i32,
+}
+
+impl Segment {
+	fn is_horizontal(&self) -> bool {
+		self.from.1 == self.to.1
 	}
 
-	Ok((distance, steps))
+	// The coordinate of the line it lies on, and the range along it.
+	fn line(&self) -> (i32, i32, i32) {
+		let (a, b) = if self.is_horizontal() {
+			(self.from.0, self.to.0)
+		} else {
+			(self.from.1, self.to.1)
+		};
+		let fixed = if self.is_horizontal() { self.from.1 } else { self.from.0 };
+		(fixed, a.min(b), a.max(b))
+	}
+
+	// The range along its line it covers, which leaves out `from`.
+	fn covered(&self) -> (i32, i32) {
+		let (_, lo, hi) = self.line();
+		let start = if self.is_horizontal() { self.from.0 } else { self.from.1 };
+		if start == lo { (lo + 1, hi) } else { (lo, hi - 1) }
+	}
+
+	fn steps_to(&self, p: (i32, i32)) -> i32 {
+		self.steps + (p.0 - self.from.0).abs() + (p.1 - self.from.1).abs()
+	}
 }
 
-fn trace(input: &str) -> Result<HashMap<(i32, i32), i32>> {
+fn segments(input: &str, wire: usize) -> Result<Vec<Segment>> {
 	let mut p: (i32, i32) = (0, 0);
-	let mut w = HashMap::new();
-	let mut s = 1;
+	let mut s = 0;
+	let mut segments = Vec::new();
 
 	for c in input.trim().split(',') {
 		let c = c.parse::<Cmd>()?;
-		let (dx, dy) = match c.dir {
-			Dir::U => ( 0,  1),
-			Dir::D => ( 0, -1),
-			Dir::L => (-1,  0),
-			Dir::R => ( 1,  0),
+		let to = match c.dir {
+			Dir::U => (p.0, p.1 + c.len),
+			Dir::D => (p.0, p.1 - c.len),
+			Dir::L => (p.0 - c.len, p.1),
+			Dir::R => (p.0 + c.len, p.1),
 		};
-		for _ in 0..c.len {
-			p.0 += dx;
-			p.1 += dy;
+		if c.len > 0 {
+			segments.push(Segment { wire, from: p, to, steps: s });
+		}
+		p = to;
+		s += c.len;
+	}
 
-			w.entry(p).or_insert(s);
-			s += 1;
+	Ok(segments)
+}
+
+// A point two wires share, with the steps each takes to first get there.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+struct Crossing {
+	point: (i32, i32),
+	wires: (usize, usize),
+	steps: (i32, i32),
+}
+
+// The crossings, ordered by point. Perpendicular segments meet in a
+// sweep over x keeping the horizontal ones by y; parallel ones can
+// only overlap on a shared line, where just the ends of the overlap
+// and its point nearest the origin are listed.
+fn crossings(wires: &[Vec<Segment>]) -> Vec<Crossing> {
+	let all: Vec<&Segment> = wires.iter().flatten().collect();
+	// First steps of both wires, by point and pair of wires.
+	let mut found: BTreeMap<_, (i32, i32)> = BTreeMap::new();
+
+	let mut record = |a: &Segment, b: &Segment, p: (i32, i32)| {
+		if a.wire == b.wire || p == a.from || p == b.from {
+			return;
+		}
+		let (a, b) = if a.wire < b.wire { (a, b) } else { (b, a) };
+		let steps = (a.steps_to(p), b.steps_to(p));
+		let best = found.entry((p, (a.wire, b.wire))).or_insert(steps);
+		*best = (best.0.min(steps.0), best.1.min(steps.1));
+	};
+
+	// Horizontal ones go in before and out after the verticals at the
+	// same x, since ends count.
+	let mut events = Vec::new();
+	for (i, s) in all.iter().enumerate() {
+		let (_, lo, hi) = s.line();
+		if s.is_horizontal() {
+			events.push((lo, 0, i));
+			events.push((hi, 2, i));
+		} else {
+			events.push((s.from.0, 1, i));
 		}
 	}
-	
-	Ok(w)
+	events.sort();
+
+	let mut active: BTreeMap<i32, Vec<usize>> = BTreeMap::new();
+	for (x, kind, i) in events {
+		match kind {
+			0 => active.entry(all[i].from.1).or_default().push(i),
+			2 => active.get_mut(&all[i].from.1).unwrap().retain(|&j| j != i),
+			_ => {
+				let (_, lo, hi) = all[i].line();
+				for (&y, hs) in active.range(lo..=hi) {
+					for &h in hs {
+						record(all[h], all[i], (x, y));
+					}
+				}
+			},
+		}
+	}
+
+	let mut lines: HashMap<(bool, i32), Vec<usize>> = HashMap::new();
+	for (i, s) in all.iter().enumerate() {
+		lines.entry((s.is_horizontal(), s.line().0)).or_default().push(i);
+	}
+	for ((horizontal, fixed), mut on) in lines {
+		on.sort_by_key(|&i| all[i].line().1);
+		for (k, &a) in on.iter().enumerate() {
+			let (_, _, end) = all[a].line();
+			for &b in on[k + 1..].iter() {
+				if all[b].line().1 > end {
+					break;
+				}
+				let (alo, ahi) = all[a].covered();
+				let (blo, bhi) = all[b].covered();
+				let (lo, hi) = (alo.max(blo), ahi.min(bhi));
+				if lo > hi {
+					continue;
+				}
+				// Steps change linearly along a shared stretch and the
+				// distance grows away from the origin, so only its ends
+				// and its point nearest the origin can be the best.
+				for &t in [lo, hi, 0.max(lo).min(hi)].iter() {
+					let p = if horizontal { (t, fixed) } else { (fixed, t) };
+					record(all[a], all[b], p);
+				}
+			}
+		}
+	}
+
+	found.into_iter()
+		.map(|((point, wires), steps)| Crossing { point, wires, steps })
+		.collect()
+}
+
+const COLORS: [&str; 6] = ["#1f77b4", "#2ca02c", "#9467bd", "#8c564b", "#e377c2", "#17becf"];
+
+// Draws each wire as a polyline, with every crossing as a dot and the
+// closest and the fewest steps ones ringed. Strokes and dots are sized
+// off the extent, so the picture reads the same at any scale.
+fn render_svg(wires: &[Vec<Segment>]) -> String {
+	let found = crossings(wires);
+
+	let mut lo = (0, 0);
+	let mut hi = (0, 0);
+	for s in wires.iter().flatten() {
+		for p in [s.from, s.to].iter() {
+			lo = (lo.0.min(p.0), lo.1.min(p.1));
+			hi = (hi.0.max(p.0), hi.1.max(p.1));
+		}
+	}
+	let extent = (hi.0 - lo.0).max(hi.1 - lo.1).max(1) as f64;
+	let margin = extent * 0.02;
+	let (stroke, dot) = (extent / 1000.0, extent / 300.0);
+
+	// SVG's y grows downwards, so flipping it to keep "U" up.
+	let mut svg = format!(
+		"<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\" width=\"1000\">\n",
+		lo.0 as f64 - margin, -hi.1 as f64 - margin,
+		(hi.0 - lo.0) as f64 + 2.0 * margin, (hi.1 - lo.1) as f64 + 2.0 * margin,
+	);
+	svg += &format!("<rect x=\"{}\" y=\"{}\" width=\"100%\" height=\"100%\" fill=\"white\"/>\n", lo.0 as f64 - margin, -hi.1 as f64 - margin);
+
+	for (i, wire) in wires.iter().enumerate() {
+		let mut points = vec!["0,0".to_string()];
+		points.extend(wire.iter().map(|s| format!("{},{}", s.to.0, -s.to.1)));
+		svg += &format!(
+			"<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\" stroke-opacity=\"0.8\"/>\n",
+			points.join(" "), COLORS[i % COLORS.len()], stroke,
+		);
+	}
+
+	for c in found.iter() {
+		svg += &format!("<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"black\"/>\n", c.point.0, -c.point.1, dot);
+	}
+
+	let closest = found.iter().min_by_key(|c| (c.point.0.abs() + c.point.1.abs(), c.point));
+	let shortest = found.iter().min_by_key(|c| (c.steps.0 + c.steps.1, c.point));
+	let marks = [
+		(closest, "red", format!("distance {}", closest.map_or(0, |c| c.point.0.abs() + c.point.1.abs()))),
+		(shortest, "orange", format!("steps {}", shortest.map_or(0, |c| c.steps.0 + c.steps.1))),
+	];
+	for (i, (c, color, label)) in marks.iter().enumerate() {
+		if let Some(c) = c {
+			let r = dot * (3 + i) as f64;
+			svg += &format!(
+				"<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\"><title>{}</title></circle>\n",
+				c.point.0, -c.point.1, r, color, stroke * 3.0, label,
+			);
+			svg += &format!(
+				"<text x=\"{}\" y=\"{}\" font-size=\"{}\" fill=\"{}\">{}</text>\n",
+				c.point.0 as f64 + r, -c.point.1 as f64 - r, dot * 4.0, color, label,
+			);
+		}
+	}
+
+	svg += &format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"black\"/>\n", -dot, -dot, 2.0 * dot, 2.0 * dot);
+	svg += "</svg>\n";
+	svg
 }
 
 #[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
@@ -97,6 +303,51 @@ impl FromStr for Cmd {
 mod tests {
 	use super::*;
 
+	// Point by point, as a reference.
+	fn solve(path1: &str, path2: &str) -> Result<(i32, i32)> {
+		let w1 = trace(&path1)?;
+		let w2 = trace(&path2)?;
+
+		let mut distance = std::i32::MAX;
+		let mut steps = std::i32::MAX;
+		
+		for (p, s1) in &w1 {
+			if let Some(s2) = w2.get(p) {
+				let d = p.0.abs() + p.1.abs();
+				distance = distance.min(d);
+
+				steps = steps.min(s1 + s2);
+			}
+		}
+
+		Ok((distance, steps))
+	}
+
+	fn trace(input: &str) -> Result<HashMap<(i32, i32), i32>> {
+		let mut p: (i32, i32) = (0, 0);
+		let mut w = HashMap::new();
+		let mut s = 1;
+
+		for c in input.trim().split(',') {
+			let c = c.parse::<Cmd>()?;
+			let (dx, dy) = match c.dir {
+				Dir::U => ( 0,  1),
+				Dir::D => ( 0, -1),
+				Dir::L => (-1,  0),
+				Dir::R => ( 1,  0),
+			};
+			for _ in 0..c.len {
+				p.0 += dx;
+				p.1 += dy;
+
+				w.entry(p).or_insert(s);
+				s += 1;
+			}
+		}
+		
+		Ok(w)
+	}
+
 	#[test]
 	fn cmd_parsing() {
 		assert_eq!("U1".parse().ok(),    Some(Cmd { dir: Dir::U, len: 1 }));
@@ -109,20 +360,83 @@ mod tests {
 	fn example1() {
 		let path1 = "R8,U5,L5,D3";
 		let path2 = "U7,R6,D4,L4";
-		assert_eq!(solve(&path1, &path2).ok(), Some((6, 30)));
+		assert_eq!(solve_many(&[path1, path2]).ok(), Some((6, 30)));
 	}
 
 	#[test]
 	fn example2() {
 		let path1 = "R75,D30,R83,U83,L12,D49,R71,U7,L72";
 		let path2 = "U62,R66,U55,R34,D71,R55,D58,R83";
-		assert_eq!(solve(&path1, &path2).ok(), Some((159, 610)));
+		assert_eq!(solve_many(&[path1, path2]).ok(), Some((159, 610)));
 	}
 
 	#[test]
 	fn example3() {
 		let path1 = "R98,U47,R26,D63,R33,U87,L62,D20,R33,U53,R51";
 		let path2 = "U98,R91,D20,R16,D67,R40,U7,R15,U6,R7";
-		assert_eq!(solve(&path1, &path2).ok(), Some((135, 410)));
+		assert_eq!(solve_many(&[path1, path2]).ok(), Some((135, 410)));
+	}
+
+	#[test]
+	fn segmented() {
+		let examples = [
+			("R8,U5,L5,D3", "U7,R6,D4,L4"),
+			("R75,D30,R83,U83,L12,D49,R71,U7,L72", "U62,R66,U55,R34,D71,R55,D58,R83"),
+			("R98,U47,R26,D63,R33,U87,L62,D20,R33,U53,R51", "U98,R91,D20,R16,D67,R40,U7,R15,U6,R7"),
+			// Running along each other, and back through the origin.
+			("R10,U3,L4,D5", "U2,R8,D2,L20,U1,R30"),
+			("R5,L5,U3", "U5,D8"),
+		];
+		for &(path1, path2) in examples.iter() {
+			assert_eq!(solve_many(&[path1, path2]).ok(), solve(path1, path2).ok(), "{} {}", path1, path2);
+		}
+	}
+
+	#[test]
+	fn many_wires() {
+		let paths = ["R8,U5,L5,D3", "U7,R6,D4,L4", "L2,U4,R10"];
+		let wires: Vec<_> = paths.iter().enumerate().map(|(i, p)| segments(p, i).unwrap()).collect();
+		let found = crossings(&wires);
+
+		// Pairwise, against the point by point tracing.
+		for i in 0..paths.len() {
+			for j in i + 1..paths.len() {
+				let (wi, wj) = (trace(paths[i]).unwrap(), trace(paths[j]).unwrap());
+				let mut expected: Vec<_> = wi.iter()
+					.filter_map(|(&p, &si)| wj.get(&p).map(|&sj| Crossing { point: p, wires: (i, j), steps: (si, sj) }))
+					.collect();
+				expected.sort();
+				let got: Vec<_> = found.iter().cloned().filter(|c| c.wires == (i, j)).collect();
+				assert_eq!(got, expected);
+			}
+		}
+
+		assert_eq!(solve_many(&paths).ok(), Some((4, 12)));
+
+		// Only the ends of a shared stretch show up.
+		let wires = vec![segments("R10", 0).unwrap(), segments("U1,R3,D1,R4", 1).unwrap()];
+		let points: Vec<_> = crossings(&wires).iter().map(|c| (c.point, c.steps)).collect();
+		assert_eq!(points, [((3, 0), (3, 5)), ((4, 0), (4, 6)), ((7, 0), (7, 9))]);
+		assert_eq!(solve_many(&paths[..2]).ok(), Some((6, 30)));
+		assert!(solve_many(&paths[..1]).is_err());
+		assert!(solve_many(&[]).is_err());
+		assert!(solve_many(&["R2,U2", "L2,D2"]).is_err());
+	}
+
+	#[test]
+	fn drawing() {
+		let wires = vec![segments("R8,U5,L5,D3", 0).unwrap(), segments("U7,R6,D4,L4", 1).unwrap()];
+		let svg = render_svg(&wires);
+
+		assert!(svg.starts_with("<svg "));
+		assert!(svg.contains("viewBox=\"-0.16 -7.16 8.32 7.32\""));
+		assert!(svg.contains("points=\"0,0 8,0 8,-5 3,-5 3,-2\""));
+		assert!(svg.contains("points=\"0,0 0,-7 6,-7 6,-3 2,-3\""));
+		assert_eq!(svg.matches("<polyline").count(), 2);
+		// Two crossings, each ringed once more.
+		assert_eq!(svg.matches("<circle").count(), 4);
+		assert!(svg.contains("<circle cx=\"3\" cy=\"-3\"") && svg.contains("<circle cx=\"6\" cy=\"-5\""));
+		assert!(svg.contains(">distance 6</text>"));
+		assert!(svg.contains(">steps 30</text>"));
 	}
 }
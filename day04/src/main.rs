@@ -1,118 +1,490 @@
+use std::io::{self, Read};
+use std::collections::HashMap;
+use std::env;
+use std::str::FromStr;
+
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Result<T, E = Error> = std::result::Result<T, E>;
 
+// Usage: day04 [lo-hi] [--base B] [--digits N] [--rule R]... [--list | --brute]
+// The range comes from stdin when not given; --brute checks every
+// number instead of counting.
 fn main() -> Result<()> {
-	let input = "146810-612564";
+	let mut range = None;
+	let mut list = false;
+	let mut brute = false;
+	let mut base = 10;
+	let mut size = None;
+	let mut rules = Vec::new();
+
+	let mut args = env::args().skip(1);
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--base" => base = args.next().ok_or("missing base")?.parse()?,
+			"--digits" => size = Some(args.next().ok_or("missing digit count")?.parse()?),
+			"--rule" => rules.push(args.next().ok_or("missing rule")?.parse::<Rule>()?),
+			"--list" => list = true,
+			"--brute" => brute = true,
+			_ => range = Some(arg),
+		}
+	}
 
-	let mut parts = input.split('-');
-	let lo: u32 = parts.next().ok_or("failed to parse from")?.parse()?;
-	let hi: u32 = parts.next().ok_or("failed to parse to")?.parse()?;
+	let input = match range {
+		Some(range) => range,
+		None => {
+			let mut input = String::new();
+			io::stdin().read_to_string(&mut input)?;
+			input
+		},
+	};
 
-	let (p1, p2) = solve(lo, hi);
-	println!("p1 = {}", p1);
-	println!("p2 = {}", p2);
+	if !(2..=36).contains(&base) {
+		return Err(format!("unsupported base {}", base).into());
+	}
+	let (lo, hi) = parse_range(&input, base)?;
+	let format = Format::fit(base, size, hi)?;
+	if rules.len() > MAX_RULES {
+		return Err(format!("at most {} rules can be combined", MAX_RULES).into());
+	}
+	if list && rules.is_empty() {
+		return Err("--list needs a --rule to list matches of".into());
+	}
+
+	let count = |rules: &[Rule]| if brute {
+		solve(lo, hi, format, rules)
+	} else {
+		Counter::from(format, rules).count(lo, hi)
+	};
+
+	if rules.is_empty() {
+		println!("p1 = {}", count(&PART1));
+		println!("p2 = {}", count(&PART2));
+	} else if list {
+		for x in Counter::from(format, &rules).matches(lo, hi) {
+			println!("{}", x);
+		}
+	} else {
+		println!("{}", count(&rules));
+	}
 
 	Ok(())
 }
 
-const SIZE: usize = 6;
-type Parts = [u8; SIZE];
+fn parse_range(input: &str, base: u64) -> Result<(u64, u64)> {
+	let mut parts = input.trim().split('-');
+	let lo = u64::from_str_radix(parts.next().ok_or("failed to parse from")?, base as u32)?;
+	let hi = u64::from_str_radix(parts.next().ok_or("failed to parse to")?, base as u32)?;
+	if parts.next().is_some() || lo > hi {
+		return Err(format!("bad range {}", input.trim()).into());
+	}
+	Ok((lo, hi))
+}
 
-fn solve(lo: u32, hi: u32) -> (u32, u32) {
-	let mut p1 = 0;
-	let mut p2 = 0;
+// How the passwords are written: `size` digits in `base`, zero padded.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+struct Format {
+	base: u64,
+	size: usize,
+}
 
-	for x in lo..=hi {
-		let parts = split(x);
-		if verify1(&parts) {
-			p1 += 1;
+impl Format {
+	// Exactly `size` digits, or as many as `hi` needs; too few would
+	// drop its leading digits. The counter works out the numbers under a
+	// prefix in u128, so `base^size` has to fit in it.
+	fn fit(base: u64, size: Option<usize>, hi: u64) -> Result<Format> {
+		let needed = width(hi, base);
+		let size = size.unwrap_or(needed);
+		if size < needed {
+			return Err(format!("{} needs {} digits, not {}", hi, needed, size).into());
 		}
-		if verify2(&parts) {
-			p2 += 1;
+		let fits = size <= 128 && (base as u128).checked_pow(size as u32).is_some();
+		if !fits {
+			return Err(format!("{} digits in base {} do not fit in 128 bits", size, base).into());
 		}
+		Ok(Format { base, size })
 	}
+}
 
-	(p1, p2)
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum Rule {
+	// Digits never decrease going left to right.
+	NonDecreasing,
+	// Some digit repeats exactly n times in a row.
+	RunExactly(usize),
+	// Some digit repeats at least n times in a row.
+	RunAtLeast(usize),
 }
 
-fn split(mut x: u32) -> Parts {
-	let mut res: Parts = [0; SIZE];
+const PART1: [Rule; 2] = [Rule::NonDecreasing, Rule::RunAtLeast(2)];
+const PART2: [Rule; 2] = [Rule::NonDecreasing, Rule::RunExactly(2)];
 
-	let mut i = SIZE;
-	while x > 0 {
-		i -= 1;
-		res[i] = (x % 10) as u8;
-		x /= 10;
+// One bit each in `Prefix::met`.
+const MAX_RULES: usize = 32;
+
+impl Rule {
+	fn check(&self, digits: &[u8]) -> bool {
+		match *self {
+			Rule::NonDecreasing => digits.windows(2).all(|w| w[0] <= w[1]),
+			Rule::RunExactly(n) => runs(digits).any(|run| run == n),
+			Rule::RunAtLeast(n) => runs(digits).any(|run| run >= n),
+		}
 	}
-	
-	return res;
 }
 
-fn verify1(x: &Parts) -> bool {
-	verify_adjacent(x) && verify_increasing(x)
+// "increasing", "run=N" or "run>=N".
+impl FromStr for Rule {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Rule> {
+		if s == "increasing" {
+			Ok(Rule::NonDecreasing)
+		} else if let Some(n) = s.strip_prefix("run>=") {
+			Ok(Rule::RunAtLeast(n.parse()?))
+		} else if let Some(n) = s.strip_prefix("run=") {
+			Ok(Rule::RunExactly(n.parse()?))
+		} else {
+			Err(format!("unknown rule {}", s).into())
+		}
+	}
 }
 
-// 012345
-// aa____
-// _aa___
-// __aa__
-// ___aa_
-// ____aa
-fn verify_adjacent(x: &Parts) -> bool {
-	for i in 0..=4 {
-		if x[i] == x[i + 1] {
-			return true;
+// Lengths of the runs of equal digits.
+fn runs(digits: &[u8]) -> impl Iterator<Item = usize> + '_ {
+	let mut i = 0;
+	std::iter::from_fn(move || {
+		if i == digits.len() {
+			return None;
+		}
+		let start = i;
+		while i < digits.len() && digits[i] == digits[start] {
+			i += 1;
+		}
+		Some(i - start)
+	})
+}
+
+fn check(rules: &[Rule], digits: &[u8]) -> bool {
+	rules.iter().all(|r| r.check(digits))
+}
+
+fn solve(lo: u64, hi: u64, format: Format, rules: &[Rule]) -> u64 {
+	let mut count = 0;
+
+	for x in lo..=hi {
+		if check(rules, &split(x, format)) {
+			count += 1;
 		}
 	}
 
-	false
+	count
 }
 
-fn verify_increasing(x: &Parts) -> bool {
-	for i in 1..SIZE {
-		if x[i] < x[i - 1] {
-			return false;
+// Where a number is after its first `pos` digits: the last digit, how
+// long its run is so far (capped past the longest length any rule looks
+// for) and which rules are already met.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+struct Prefix {
+	pos: usize,
+	last: Option<u8>,
+	run: usize,
+	met: u32,
+}
+
+// Counts passwords digit by digit: numbers sharing a prefix state have
+// the same ways to finish, so each state is counted once.
+struct Counter<'a> {
+	format: Format,
+	rules: &'a [Rule],
+	cap: usize,
+	memo: HashMap<Prefix, u64>,
+}
+
+impl<'a> Counter<'a> {
+	fn from(format: Format, rules: &'a [Rule]) -> Counter<'a> {
+		let cap = rules.iter().map(|r| match *r {
+			Rule::RunExactly(n) | Rule::RunAtLeast(n) => n + 1,
+			Rule::NonDecreasing => 1,
+		}).max().unwrap_or(1);
+
+		Counter { format, rules, cap, memo: HashMap::new() }
+	}
+
+	fn start(&self) -> Prefix {
+		let mut met = 0;
+		for (i, r) in self.rules.iter().enumerate() {
+			if *r == Rule::NonDecreasing {
+				met |= 1 << i;
+			}
+		}
+		Prefix { pos: 0, last: None, run: 0, met }
+	}
+
+	// Marks the rules met by a run ending, or growing, to `run` digits.
+	fn close(&self, mut met: u32, run: usize, ended: bool) -> u32 {
+		for (i, r) in self.rules.iter().enumerate() {
+			let ok = match *r {
+				Rule::RunExactly(n) => ended && run == n,
+				Rule::RunAtLeast(n) => run >= n,
+				Rule::NonDecreasing => true,
+			};
+			if ok {
+				met |= 1 << i;
+			}
+		}
+		met
+	}
+
+	fn step(&self, p: Prefix, d: u8) -> Option<Prefix> {
+		let unordered = !self.rules.contains(&Rule::NonDecreasing);
+		match p.last {
+			Some(last) if d < last && !unordered => None,
+			Some(last) if d == last => {
+				let run = (p.run + 1).min(self.cap);
+				Some(Prefix { pos: p.pos + 1, run, met: self.close(p.met, run, false), ..p })
+			},
+			Some(_) => {
+				let met = self.close(p.met, p.run, true);
+				Some(Prefix { pos: p.pos + 1, last: Some(d), run: 1, met: self.close(met, 1, false) })
+			},
+			None => Some(Prefix { pos: 1, last: Some(d), run: 1, met: self.close(p.met, 1, false) }),
 		}
 	}
 
-	true
+	fn accepts(&self, p: Prefix) -> bool {
+		self.close(p.met, p.run, true).count_ones() as usize == self.rules.len()
+	}
+
+	// Ways to finish `p` into a password.
+	fn completions(&mut self, p: Prefix) -> u64 {
+		if p.pos == self.format.size {
+			return self.accepts(p) as u64;
+		}
+		if let Some(&n) = self.memo.get(&p) {
+			return n;
+		}
+
+		let mut n = 0;
+		for d in 0..self.format.base as u8 {
+			if let Some(next) = self.step(p, d) {
+				n += self.completions(next);
+			}
+		}
+		self.memo.insert(p, n);
+		n
+	}
+
+	// The numbers `p` can finish into, given its digits so far.
+	fn span(&self, p: Prefix, value: u128) -> (u128, u128) {
+		let scale = (self.format.base as u128).pow((self.format.size - p.pos) as u32);
+		(value * scale, (value + 1) * scale - 1)
+	}
+
+	fn count(&mut self, lo: u64, hi: u64) -> u64 {
+		let start = self.start();
+		self.count_within(start, 0, lo as u128, hi as u128)
+	}
+
+	// Only prefixes straddling a bound of the range get split further.
+	fn count_within(&mut self, p: Prefix, value: u128, lo: u128, hi: u128) -> u64 {
+		let (a, b) = self.span(p, value);
+		if b < lo || hi < a {
+			return 0;
+		}
+		if lo <= a && b <= hi {
+			return self.completions(p);
+		}
+
+		let mut n = 0;
+		for d in 0..self.format.base as u8 {
+			if let Some(next) = self.step(p, d) {
+				n += self.count_within(next, value * self.format.base as u128 + d as u128, lo, hi);
+			}
+		}
+		n
+	}
+
+	// The passwords in the range, in order, found as they are asked for.
+	fn matches(self, lo: u64, hi: u64) -> Matches<'a> {
+		let start = self.start();
+		Matches { counter: self, lo: lo as u128, hi: hi as u128, stack: vec![(start, 0, 0)] }
+	}
 }
 
-fn verify2(x: &Parts) -> bool {
-	verify_adjacent_not_larger(x) && verify_increasing(x)
+struct Matches<'a> {
+	counter: Counter<'a>,
+	lo: u128,
+	hi: u128,
+	// Prefix, its value and the next digit to try after it.
+	stack: Vec<(Prefix, u128, u8)>,
 }
 
-// 012345
-// aabbbb
-// baabbb
-// bbaabb
-// bbbaab
-// bbbbaa
-fn verify_adjacent_not_larger(x: &Parts) -> bool {
-	for i in 0..=4 {
-		if x[i] == x[i + 1] && (i == 0 || x[i] != x[i - 1]) && (i == 4 || x[i] != x[i + 2]) {
-			return true;
+impl<'a> Iterator for Matches<'a> {
+	type Item = u64;
+
+	fn next(&mut self) -> Option<u64> {
+		let base = self.counter.format.base as u8;
+		while let Some(top) = self.stack.last_mut() {
+			let (p, value, d) = *top;
+			if p.pos == self.counter.format.size {
+				self.stack.pop();
+				return Some(value as u64);
+			}
+			if d == base {
+				self.stack.pop();
+				continue;
+			}
+			top.2 += 1;
+
+			// Skipping prefixes outside the range or with no way to finish.
+			if let Some(next) = self.counter.step(p, d) {
+				let value = value * base as u128 + d as u128;
+				let (a, b) = self.counter.span(next, value);
+				if b < self.lo || self.hi < a || self.counter.completions(next) == 0 {
+					continue;
+				}
+				self.stack.push((next, value, 0));
+			}
 		}
+		None
+	}
+}
+
+// Digits needed to write `x` in `base`.
+fn width(mut x: u64, base: u64) -> usize {
+	let mut n = 1;
+	while x >= base {
+		x /= base;
+		n += 1;
 	}
+	n
+}
+
+fn split(mut x: u64, format: Format) -> Vec<u8> {
+	let mut res = vec![0; format.size];
 
-	false
+	let mut i = format.size;
+	while x > 0 && i > 0 {
+		i -= 1;
+		res[i] = (x % format.base) as u8;
+		x /= format.base;
+	}
+	
+	res
 }
 
 #[cfg(test)]
 mod tests {
 	use super::*;
 
+	const DECIMAL: Format = Format { base: 10, size: 6 };
+
 	#[test]
 	fn examples1() {
-		assert_eq!(verify1(&split(111111)), true);
-		assert_eq!(verify1(&split(223450)), false);
-		assert_eq!(verify1(&split(123789)), false);
+		assert!(check(&PART1, &split(111111, DECIMAL)));
+		assert!(!check(&PART1, &split(223450, DECIMAL)));
+		assert!(!check(&PART1, &split(123789, DECIMAL)));
 	}
 
 	#[test]
 	fn examples2() {
-		assert_eq!(verify2(&split(112233)), true);
-		assert_eq!(verify2(&split(123444)), false);
-		assert_eq!(verify2(&split(111122)), true);
+		assert!(check(&PART2, &split(112233, DECIMAL)));
+		assert!(!check(&PART2, &split(123444, DECIMAL)));
+		assert!(check(&PART2, &split(111122, DECIMAL)));
+	}
+
+	#[test]
+	fn rules() {
+		assert_eq!(runs(&[1, 1, 2, 3, 3, 3]).collect::<Vec<_>>(), [2, 1, 3]);
+		assert_eq!(runs(&[]).count(), 0);
+
+		let rules: Vec<Rule> = ["increasing", "run=3", "run>=2"].iter().map(|r| r.parse().unwrap()).collect();
+		assert_eq!(rules, [Rule::NonDecreasing, Rule::RunExactly(3), Rule::RunAtLeast(2)]);
+		assert!("run<2".parse::<Rule>().is_err());
+
+		assert!(check(&rules, &[1, 1, 1, 4]));
+		assert!(!check(&rules, &[1, 1, 1, 1]));
+		assert!(!check(&rules, &[2, 2, 2, 1]));
+	}
+
+	#[test]
+	fn formats() {
+		let hex = Format { base: 16, size: 4 };
+		assert_eq!(split(0xabc, hex), [0, 10, 11, 12]);
+		assert_eq!(width(0xffff, 16), 4);
+		assert_eq!(width(0, 10), 1);
+		assert_eq!(parse_range("0ff0-1000", 16).ok(), Some((0xff0, 0x1000)));
+		assert!(parse_range("20-10", 10).is_err());
+		assert_eq!(Format::fit(16, None, 0xabc).ok(), Some(Format { base: 16, size: 3 }));
+		assert_eq!(Format::fit(10, Some(8), 612564).ok(), Some(Format { base: 10, size: 8 }));
+		assert!(Format::fit(10, Some(5), 612564).is_err());
+		assert!(Format::fit(10, Some(38), 612564).is_ok());
+		assert!(Format::fit(10, Some(40), 612564).is_err());
+		assert!(Format::fit(2, Some(127), 1).is_ok());
+		assert!(Format::fit(2, Some(128), 1).is_err());
+
+		// The increasing ones are 0000, 0001, 0011, 0111 and 1111, only
+		// 0011 has a run of exactly two.
+		let binary = Format { base: 2, size: 4 };
+		assert_eq!(solve(0, 15, binary, &PART1), 5);
+		assert_eq!(solve(0, 15, binary, &PART2), 1);
+
+		assert_eq!(solve(146810, 612564, DECIMAL, &PART1), 1748);
+	}
+
+	#[test]
+	fn counting() {
+		let (lo, hi) = (146810, 612564);
+		for rules in [&PART1[..], &PART2[..]].iter() {
+			assert_eq!(Counter::from(DECIMAL, rules).count(lo, hi), solve(lo, hi, DECIMAL, rules));
+		}
+
+		// Odd combinations, small bases, leading zeros.
+		let sets: [&[Rule]; 5] = [
+			&[],
+			&[Rule::RunExactly(3)],
+			&[Rule::RunAtLeast(2), Rule::RunExactly(1)],
+			&[Rule::NonDecreasing, Rule::RunExactly(2), Rule::RunExactly(3)],
+			&[Rule::RunAtLeast(4), Rule::NonDecreasing],
+		];
+		for &base in [2, 3, 5].iter() {
+			let format = Format { base, size: 7 };
+			let top = base.pow(7) - 1;
+			for rules in sets.iter() {
+				let mut counter = Counter::from(format, rules);
+				for &(lo, hi) in [(0, top), (1, top / 3), (top / 2, top / 2), (17, top - 17)].iter() {
+					assert_eq!(counter.count(lo, hi), solve(lo, hi, format, rules), "{} {:?} {}-{}", base, rules, lo, hi);
+				}
+			}
+		}
+
+		// Twelve digits, far too many to check one by one.
+		let twelve = Format { base: 10, size: 12 };
+		let mut counter = Counter::from(twelve, &PART2);
+		let all = counter.count(0, 999_999_999_999);
+		assert_eq!(all, counter.count(0, 499_999_999_999) + counter.count(500_000_000_000, 999_999_999_999));
+		assert_eq!(all, 235995);
+
+		// As many rules as there are bits to track them.
+		let mut many = vec![Rule::RunAtLeast(2); MAX_RULES - 1];
+		many.push(Rule::NonDecreasing);
+		assert_eq!(Counter::from(DECIMAL, &many).count(lo, hi), 1748);
+	}
+
+	#[test]
+	fn enumerating() {
+		let (lo, hi) = (146810, 612564);
+		let found: Vec<u64> = Counter::from(DECIMAL, &PART2).matches(lo, hi).collect();
+		let expected: Vec<u64> = (lo..=hi).filter(|&x| check(&PART2, &split(x, DECIMAL))).collect();
+		assert_eq!(found, expected);
+
+		let format = Format { base: 3, size: 6 };
+		let rules = [Rule::RunExactly(2)];
+		let found: Vec<u64> = Counter::from(format, &rules).matches(5, 600).collect();
+		let expected: Vec<u64> = (5..=600).filter(|&x| check(&rules, &split(x, format))).collect();
+		assert_eq!(found, expected);
+
+		let twelve = Format { base: 10, size: 12 };
+		let first: Vec<u64> = Counter::from(twelve, &PART2).matches(123_456_789_012, 999_999_999_999).take(3).collect();
+		assert_eq!(first, [123_456_888_899, 123_456_889_999, 123_457_777_788]);
 	}
 }
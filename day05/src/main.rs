@@ -1,12 +1,33 @@
 use std::io::{self, Read};
+use std::collections::HashSet;
+use std::env;
+use std::ops::Range;
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Result<T, E = Error> = std::result::Result<T, E>;
 
+const TRACE: bool = false;
+
+macro_rules! trace {
+	($($arg:tt)+) => {
+		if TRACE {
+			println!($($arg)+);
+		}
+	};
+}
+
 fn main() -> Result<()> {
 	let mut input = String::new();
 	io::stdin().read_to_string(&mut input)?;
 
+	if env::args().nth(1).as_deref() == Some("diagnose") {
+		for &id in [1, 5].iter() {
+			let report = diagnose(&input, id)?;
+			print!("{}", report.describe(id));
+		}
+		return Ok(());
+	}
+
 	let p1 = run_sub(&input, 1)?;
 	let p2 = run_sub(&input, 5)?;
 	println!("p1: {:?}", p1);
@@ -31,7 +52,137 @@ fn parse(input: &str) -> Result<Box<[isize]>> {
 	Ok(ram.into_boxed_slice())
 }
 
-fn execute(mut input: &[isize], ram: &mut [isize]) -> Vec<isize> {
+// A failed test from a TEST diagnostic run.
+#[derive(PartialEq, Eq, Clone, Debug)]
+struct Failure {
+	// Which output reported it.
+	test: usize,
+	code: isize,
+	// Addresses of the instructions run for the test.
+	range: Range<usize>,
+	// Opcodes, with their modes, the test was the first to use.
+	under_test: Vec<isize>,
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+struct Report {
+	outputs: Vec<isize>,
+	failures: Vec<Failure>,
+}
+
+impl Report {
+	// The final output, once every test before it passed.
+	fn code(&self) -> Option<isize> {
+		if self.failures.is_empty() {
+			self.outputs.last().cloned()
+		} else {
+			None
+		}
+	}
+
+	fn describe(&self, id: isize) -> String {
+		let mut text = String::new();
+		let tests = self.outputs.len().saturating_sub(1);
+		for f in self.failures.iter() {
+			let ops: Vec<String> = f.under_test.iter().map(|&op| mnemonic(op)).collect();
+			text += &format!("system {}: test {} failed with {} at {}..{}, testing {}\n",
+				id, f.test, f.code, f.range.start, f.range.end, ops.join("; "));
+		}
+		match self.code() {
+			Some(code) => text += &format!("system {}: {} tests passed, diagnostic code {}\n", id, tests, code),
+			None => text += &format!("system {}: {} of {} tests failed\n", id, self.failures.len(), tests),
+		}
+		text
+	}
+}
+
+// Runs the TEST diagnostics for system `id`. Each output but the last is
+// a test result, 0 when it passed; a test is made of the instructions
+// run since the previous output.
+fn diagnose(program: &str, id: isize) -> Result<Report> {
+	let mut ram = parse(program)?;
+
+	let mut tests = vec![Vec::new()];
+	let outputs = execute_with(&[id], &mut ram, &mut |pc, opcode| {
+		tests.last_mut().unwrap().push((pc, opcode));
+		if opcode % 100 == 4 {
+			tests.push(Vec::new());
+		}
+	});
+
+	let mut seen = HashSet::new();
+	let mut failures = Vec::new();
+	for (test, (steps, &code)) in tests.iter().zip(outputs.iter()).enumerate() {
+		let mut under_test = Vec::new();
+		for &(_, opcode) in steps.iter() {
+			if seen.insert(opcode) {
+				under_test.push(opcode);
+			}
+		}
+
+		if code == 0 || test + 1 == outputs.len() {
+			continue;
+		}
+
+		let start = steps.iter().map(|&(pc, _)| pc).min().unwrap_or(0);
+		let end = steps.iter().map(|&(pc, opcode)| pc + width(opcode)).max().unwrap_or(0);
+
+		// Nothing new, so blaming whatever computed the result.
+		if under_test.is_empty() {
+			under_test.extend(steps.iter().rev().map(|&(_, opcode)| opcode).find(|op| op % 100 != 4));
+		}
+
+		failures.push(Failure { test, code, range: start..end, under_test });
+	}
+
+	Ok(Report { outputs, failures })
+}
+
+fn width(opcode: isize) -> usize {
+	match opcode % 100 {
+		1 | 2 | 7 | 8 => 4,
+		5 | 6 => 3,
+		3 | 4 => 2,
+		_ => 1,
+	}
+}
+
+// Opcode name with its parameter modes, "add imm,pos,pos" style.
+fn mnemonic(opcode: isize) -> String {
+	let name = match opcode % 100 {
+		1 => "add",
+		2 => "mul",
+		3 => "in",
+		4 => "out",
+		5 => "jt",
+		6 => "jf",
+		7 => "lt",
+		8 => "eq",
+		99 => "halt",
+		_ => "unknown",
+	};
+
+	let mut modes = Vec::new();
+	let mut m = opcode / 100;
+	for _ in 1..width(opcode) {
+		modes.push(if m % 10 == 1 { "imm" } else { "pos" });
+		m /= 10;
+	}
+
+	if modes.is_empty() {
+		name.to_string()
+	} else {
+		format!("{} {}", name, modes.join(","))
+	}
+}
+
+fn execute(input: &[isize], ram: &mut [isize]) -> Vec<isize> {
+	execute_with(input, ram, &mut |_, _| {})
+}
+
+// Runs the program, telling `observe` the address and the opcode of each
+// instruction before it executes.
+fn execute_with(mut input: &[isize], ram: &mut [isize], observe: &mut dyn FnMut(usize, isize)) -> Vec<isize> {
 	let mut pc: usize = 0;
 
 	let mut output = Vec::new();
@@ -39,7 +190,8 @@ fn execute(mut input: &[isize], ram: &mut [isize]) -> Vec<isize> {
 	loop {
 		let (op, len) = decode(&ram[pc..]);
 
-		println!("{:?}", op);
+		trace!("{:?}", op);
+		observe(pc, ram[pc]);
 		
 		match op {
 			Op::Add(a, b, Param::Pos(c)) => {
@@ -56,19 +208,19 @@ fn execute(mut input: &[isize], ram: &mut [isize]) -> Vec<isize> {
 				let x = input[0];
 				input = &input[1..];
 				ram[d] = x;
-				println!(";; read {}", x);
+				trace!(";; read {}", x);
 			},
 			Op::Out(a) => {
 				let a = load_value(&ram, a);
 				output.push(a);
-				println!(";; wrote {}", a);
+				trace!(";; wrote {}", a);
 			},
 			Op::JmpTrue(a, b) => {
 				let a = load_value(&ram, a);
 				let b = load_value(&ram, b);
 				if a != 0 {
 					pc = b as usize;
-					println!(";; jumped to {}", pc);
+					trace!(";; jumped to {}", pc);
 					continue;
 				}
 			},
@@ -77,7 +229,7 @@ fn execute(mut input: &[isize], ram: &mut [isize]) -> Vec<isize> {
 				let b = load_value(&ram, b);
 				if a == 0 {
 					pc = b as usize;
-					println!(";; jumped to {}", pc);
+					trace!(";; jumped to {}", pc);
 					continue;
 				}
 			},
@@ -85,13 +237,13 @@ fn execute(mut input: &[isize], ram: &mut [isize]) -> Vec<isize> {
 				let a = load_value(&ram, a);
 				let b = load_value(&ram, b);
 				ram[c] = if a < b { 1 } else { 0 };
-				println!(";; wrote {}", ram[c]);
+				trace!(";; wrote {}", ram[c]);
 			},
 			Op::CmpEq(a, b, Param::Pos(c)) => {
 				let a = load_value(&ram, a);
 				let b = load_value(&ram, b);
 				ram[c] = if a == b { 1 } else { 0 };
-				println!(";; wrote {}", ram[c]);
+				trace!(";; wrote {}", ram[c]);
 			},
 			Op::Halt => break,
 			_ => panic!("unknown opcode"),
@@ -360,6 +512,29 @@ mod tests {
 		assert_eq!(output[0], 1);
 	}
 
+	#[test]
+	fn diagnostics() {
+		// Tests "2 + 3 - 5", "2 * 3 - 7" and "6 < 7", then reports 77.
+		let program = "3,0,1101,2,3,31,1001,31,-5,31,4,31,1102,2,3,32,1001,32,-7,32,4,32,1107,6,7,33,4,33,104,77,99,0,0,0";
+		let report = diagnose(program, 1).unwrap();
+		assert_eq!(report.outputs, [0, -1, 1, 77]);
+		assert_eq!(report.code(), None);
+		assert_eq!(report.failures, [
+			Failure { test: 1, code: -1, range: 12..22, under_test: vec![1102] },
+			Failure { test: 2, code: 1, range: 22..28, under_test: vec![1107] },
+		]);
+		assert_eq!(report.describe(1).lines().next(), Some("system 1: test 1 failed with -1 at 12..22, testing mul imm,imm,pos"));
+
+		let program = "3,0,1101,2,3,15,1001,15,-5,15,4,15,104,77,99,0";
+		let report = diagnose(program, 1).unwrap();
+		assert_eq!(report.code(), Some(77));
+		assert_eq!(report.describe(1), "system 1: 1 tests passed, diagnostic code 77\n");
+
+		assert_eq!(mnemonic(3), "in pos");
+		assert_eq!(mnemonic(1005), "jt pos,imm");
+		assert_eq!(mnemonic(99), "halt");
+	}
+
 	#[test]
 	fn example9_lt() {
 		let mut ram = parse(&"3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99").unwrap();
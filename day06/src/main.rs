@@ -1,5 +1,7 @@
 use std::io::{self, Read};
 use std::collections::HashMap;
+use std::env;
+use std::fmt;
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Result<T, E = Error> = std::result::Result<T, E>;
@@ -9,57 +11,242 @@ fn main() -> Result<()> {
 	io::stdin().read_to_string(&mut input)?;
 
 	let scheme = parse(&input)?;
+
+	// `show X`, `path A B`, `dot [--path]` or `ascii [--path]` look at
+	// the tree instead; --path marks the way from YOU to SAN.
+	let args: Vec<String> = env::args().skip(1).collect();
+	if !args.is_empty() {
+		let tree = Tree::from(&scheme);
+		let id = |name: &str| scheme.id(name);
+		match &args[..] {
+			[cmd, x] if cmd == "show" => {
+				let x = id(x)?;
+				let children: Vec<&str> = tree.children[x].iter().map(|&c| scheme.items[c]).collect();
+				println!("{}: depth {}, {} objects in its subtree", scheme.items[x], tree.depth[x], tree.size[x]);
+				println!("orbits: {}", tree.parent[x].map_or("nothing", |p| scheme.items[p]));
+				println!("orbited by: {}", children.join(", "));
+			},
+			[cmd, a, b] if cmd == "path" => {
+				let (a, b) = (id(a)?, id(b)?);
+				let path = tree.path(a, b).ok_or("not connected")?;
+				let names: Vec<&str> = path.iter().map(|&i| scheme.items[i]).collect();
+				println!("{}", names.join(" -> "));
+				if let Some(steps) = tree.reach(a, b) {
+					println!("{} orbits {} {} levels down", names[0], names[names.len() - 1], steps);
+				}
+			},
+			[cmd, rest @ ..] if cmd == "dot" || cmd == "ascii" => {
+				let marked = match rest {
+					[] => Vec::new(),
+					[flag] if flag == "--path" => tree.path(id("YOU")?, id("SAN")?).ok_or("YOU and SAN are not connected")?,
+					_ => return Err(format!("unknown options {:?}", rest).into()),
+				};
+				if cmd == "dot" {
+					print!("{}", to_dot(&scheme, &tree, &marked));
+				} else {
+					print!("{}", to_ascii(&scheme, &tree, &marked));
+				}
+			},
+			_ => return Err("usage: day06 [show X | path A B | dot [--path] | ascii [--path]]".into()),
+		}
+		return Ok(());
+	}
 	
 	println!("p1: {}", solve_part1(&scheme));
-	println!("p2: {}", solve_part2(&scheme));
+	println!("p2: {}", solve_part2(&scheme)?);
 	
 	Ok(())
 }
 
-fn solve_part1(scheme: &Scheme) -> i64 {
-	let mut total = 0;
-	for (id, _) in scheme.items.iter().enumerate() {
-		total += trace(&scheme, id, |_,_| None, |steps| Some(steps)).unwrap_or(0);
-	}
-
-	total
+fn solve_part1(scheme: &Scheme) -> usize {
+	Tree::from(scheme).depth.iter().sum()
 }
 
-fn solve_part2(scheme: &Scheme) -> i64 {
-	let san_id = *scheme.to_id.get("SAN").expect("Santa node is required");
-	let you_id = *scheme.to_id.get("YOU").expect("You node is required");
+fn solve_part2(scheme: &Scheme) -> Result<usize, Problem> {
+	let san_id = scheme.id("SAN")?;
+	let you_id = scheme.id("YOU")?;
 
-	trace(
-		&scheme,
-		you_id,
-		|steps, current| { try_reach(&scheme, san_id, current).map(|s| s + steps - 2) },
-		|_| None)
-		.unwrap_or(0)
+	// Transfers go between the objects YOU and SAN orbit.
+	let tree = Tree::from(scheme);
+	Ok(tree.distance(you_id, san_id).map(|d| d.saturating_sub(2)).unwrap_or(0))
 }
 
-fn try_reach(scheme: &Scheme, from: usize, to: usize) -> Option<i64> {
-	trace(&scheme, from, |steps,id| if id == to { Some(steps) } else { None }, |_| None)
+// The orbits as a forest, indexed by object id. Roots are the objects
+// orbiting nothing; each knows its depth below its root.
+struct Tree {
+	parent: Vec<Option<usize>>,
+	children: Vec<Vec<usize>>,
+	root: Vec<usize>,
+	depth: Vec<usize>,
+	// Objects in the subtree, itself included.
+	size: Vec<usize>,
+	// up[k][id] is the ancestor 2^k levels above, or the root.
+	up: Vec<Vec<usize>>,
 }
 
-fn trace<F, U>(scheme: &Scheme, from: usize, should_stop: F, map_result: U) -> Option<i64>
-	where F: Fn(i64, usize) -> Option<i64>,
-		  U: Fn(i64) -> Option<i64>
-{
-	let mut steps = 0;
-	
-	let mut id = from;
-	loop {
-		if let Some(result) = should_stop(steps, id) {
-			return Some(result);
+impl Tree {
+	fn from(scheme: &Scheme) -> Tree {
+		let n = scheme.items.len();
+		let mut parent = vec![None; n];
+		let mut children = vec![Vec::new(); n];
+		for (&id, &center) in scheme.links.iter() {
+			parent[id] = Some(center);
+			children[center].push(id);
+		}
+		for c in children.iter_mut() {
+			c.sort();
+		}
+
+		// Parents come before their children in `order`.
+		let mut order: Vec<usize> = (0..n).filter(|&id| parent[id].is_none()).collect();
+		let mut root: Vec<usize> = (0..n).collect();
+		let mut depth = vec![0; n];
+		let mut i = 0;
+		while i < order.len() {
+			let id = order[i];
+			for &c in children[id].iter() {
+				root[c] = root[id];
+				depth[c] = depth[id] + 1;
+				order.push(c);
+			}
+			i += 1;
+		}
+
+		let mut size = vec![1; n];
+		for &id in order.iter().rev() {
+			if let Some(p) = parent[id] {
+				size[p] += size[id];
+			}
+		}
+
+		let levels = (usize::BITS - n.leading_zeros()).max(1) as usize;
+		let mut up = vec![(0..n).map(|id| parent[id].unwrap_or(id)).collect::<Vec<_>>()];
+		for k in 1..levels {
+			let next = (0..n).map(|id| up[k - 1][up[k - 1][id]]).collect();
+			up.push(next);
 		}
 
-		if let Some(center) = scheme.links.get(&id) {
-			steps += 1;
-			id = *center;
+		Tree { parent, children, root, depth, size, up }
+	}
+
+	fn ancestor(&self, mut id: usize, steps: usize) -> usize {
+		for (k, up) in self.up.iter().enumerate() {
+			if steps & (1 << k) != 0 {
+				id = up[id];
+			}
+		}
+		id
+	}
+
+	// Steps from `from` up to `to`, when `to` is among its centres.
+	fn reach(&self, from: usize, to: usize) -> Option<usize> {
+		let steps = self.depth[from].checked_sub(self.depth[to])?;
+		if self.root[from] == self.root[to] && self.ancestor(from, steps) == to {
+			Some(steps)
 		} else {
-			return map_result(steps);
+			None
+		}
+	}
+
+	fn lca(&self, a: usize, b: usize) -> Option<usize> {
+		if self.root[a] != self.root[b] {
+			return None;
+		}
+
+		let (mut a, mut b) = if self.depth[a] < self.depth[b] { (b, a) } else { (a, b) };
+		a = self.ancestor(a, self.depth[a] - self.depth[b]);
+		if a == b {
+			return Some(a);
+		}
+		for up in self.up.iter().rev() {
+			if up[a] != up[b] {
+				a = up[a];
+				b = up[b];
+			}
+		}
+		self.parent[a]
+	}
+
+	fn distance(&self, a: usize, b: usize) -> Option<usize> {
+		let c = self.lca(a, b)?;
+		Some(self.depth[a] + self.depth[b] - 2 * self.depth[c])
+	}
+
+	// Objects from `a` to `b`, both included.
+	fn path(&self, a: usize, b: usize) -> Option<Vec<usize>> {
+		let c = self.lca(a, b)?;
+
+		let mut path = Vec::new();
+		let mut id = a;
+		while id != c {
+			path.push(id);
+			id = self.parent[id]?;
+		}
+		path.push(c);
+
+		let mut back = Vec::new();
+		let mut id = b;
+		while id != c {
+			back.push(id);
+			id = self.parent[id]?;
+		}
+		path.extend(back.into_iter().rev());
+
+		Some(path)
+	}
+}
+
+// Graphviz, centres pointing at what orbits them. Objects and links in
+// `marked` are drawn in red.
+fn to_dot(scheme: &Scheme, tree: &Tree, marked: &[usize]) -> String {
+	let on = |id: usize| marked.contains(&id);
+
+	let mut dot = String::from("digraph orbits {\n\trankdir=LR;\n\tnode [shape=circle, fontsize=10];\n");
+	for id in by_name(scheme, 0..scheme.items.len()) {
+		if on(id) {
+			dot += &format!("\t\"{}\" [color=red, fontcolor=red, penwidth=2];\n", scheme.items[id]);
+		}
+	}
+	for id in by_name(scheme, 0..scheme.items.len()) {
+		if let Some(p) = tree.parent[id] {
+			let style = if on(id) && on(p) { " [color=red, penwidth=2]" } else { "" };
+			dot += &format!("\t\"{}\" -> \"{}\"{};\n", scheme.items[p], scheme.items[id], style);
 		}
 	}
+	dot += "}\n";
+	dot
+}
+
+// An indented outline from each root down, marked objects starred.
+fn to_ascii(scheme: &Scheme, tree: &Tree, marked: &[usize]) -> String {
+	let roots = (0..scheme.items.len()).filter(|&id| tree.parent[id].is_none());
+
+	let mut text = String::new();
+	// Object, the prefix for its line and the one for its children.
+	let mut stack: Vec<(usize, String, String)> = by_name(scheme, roots)
+		.into_iter()
+		.rev()
+		.map(|r| (r, String::new(), String::new()))
+		.collect();
+
+	while let Some((id, line, below)) = stack.pop() {
+		let star = if marked.contains(&id) { " *" } else { "" };
+		text += &format!("{}{}{}\n", line, scheme.items[id], star);
+
+		let children = by_name(scheme, tree.children[id].iter().cloned());
+		for (i, &c) in children.iter().enumerate().rev() {
+			let last = i + 1 == children.len();
+			let (branch, next) = if last { ("`-- ", "    ") } else { ("+-- ", "|   ") };
+			stack.push((c, format!("{}{}", below, branch), format!("{}{}", below, next)));
+		}
+	}
+	text
+}
+
+fn by_name(scheme: &Scheme, ids: impl Iterator<Item = usize>) -> Vec<usize> {
+	let mut ids: Vec<usize> = ids.collect();
+	ids.sort_by_key(|&id| scheme.items[id]);
+	ids
 }
 
 struct Scheme<'a> {
@@ -69,30 +256,153 @@ struct Scheme<'a> {
 	links: HashMap<usize, usize>,
 }
 
-fn parse<'a>(input: &'a str) -> Result<Scheme<'a>> {
+impl<'a> Scheme<'a> {
+	fn id(&self, name: &str) -> Result<usize, Problem> {
+		self.to_id.get(name).cloned().ok_or_else(|| Problem::Unknown(name.to_string()))
+	}
+}
+
+// Something wrong with an orbit map. Lines count from 1.
+#[derive(PartialEq, Eq, Clone, Debug)]
+enum Problem {
+	Syntax { line: usize, text: String },
+	Duplicate { line: usize, first: usize },
+	TwoCentres { line: usize, object: String, first: usize },
+	// Members in orbit order, with the lines linking them.
+	Cycle { members: Vec<String>, lines: Vec<usize> },
+	// Objects orbiting nothing, when that is not just COM, with the
+	// lines they first show up on.
+	Roots(Vec<(String, usize)>),
+	Unknown(String),
+}
+
+impl fmt::Display for Problem {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Problem::Syntax { line, text } => write!(f, "line {}: can't read \"{}\"", line, text),
+			Problem::Duplicate { line, first } => write!(f, "line {}: repeats line {}", line, first),
+			Problem::TwoCentres { line, object, first } => {
+				write!(f, "line {}: {} already orbits something on line {}", line, object, first)
+			},
+			Problem::Cycle { members, lines } => {
+				let lines: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+				write!(f, "lines {}: cycle through {}", lines.join(", "), members.join(", "))
+			},
+			Problem::Roots(roots) if roots.is_empty() => write!(f, "no object orbits nothing"),
+			Problem::Roots(roots) => {
+				let roots: Vec<String> = roots.iter().map(|(r, l)| format!("{} (line {})", r, l)).collect();
+				write!(f, "expected only COM to orbit nothing, got {}", roots.join(", "))
+			},
+			Problem::Unknown(name) => write!(f, "unknown object {}", name),
+		}
+	}
+}
+
+impl std::error::Error for Problem {}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+struct Problems(Vec<Problem>);
+
+impl fmt::Display for Problems {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let lines: Vec<String> = self.0.iter().map(|p| p.to_string()).collect();
+		write!(f, "{}", lines.join("\n"))
+	}
+}
+
+impl std::error::Error for Problems {}
+
+fn parse<'a>(input: &'a str) -> Result<Scheme<'a>, Problems> {
+	let (scheme, problems) = read(input);
+	if problems.is_empty() {
+		Ok(scheme)
+	} else {
+		Err(Problems(problems))
+	}
+}
+
+// Reads what it can, collecting every problem on the way.
+fn read<'a>(input: &'a str) -> (Scheme<'a>, Vec<Problem>) {
 	let mut items = Vec::new();
 	let mut to_id = HashMap::new();
 	let mut links = HashMap::new();
+
+	let mut problems = Vec::new();
+	let mut seen = HashMap::new();
+	// Where each object first shows up, and where it gets its centre.
+	let mut first_line = Vec::new();
+	let mut link_line = HashMap::new();
 	
-	for line in input.trim().lines().map(|l| l.trim()) {
+	for (n, line) in input.lines().enumerate().map(|(n, l)| (n + 1, l.trim())) {
+		if line.is_empty() {
+			continue;
+		}
+
 		let mut pair = line.split(')');
-		let a = pair.next().ok_or("failed to parse first item")?;
-		let b = pair.next().ok_or("failed to parse second item")?;
+		let (a, b) = match (pair.next(), pair.next(), pair.next()) {
+			(Some(a), Some(b), None) if !a.is_empty() && !b.is_empty() => (a, b),
+			_ => {
+				problems.push(Problem::Syntax { line: n, text: line.to_string() });
+				continue;
+			},
+		};
+		if let Some(&first) = seen.get(&(a, b)) {
+			problems.push(Problem::Duplicate { line: n, first });
+			continue;
+		}
+		seen.insert((a, b), n);
 
 		let a_id = *to_id.entry(a).or_insert_with(|| {
 			items.push(a);
+			first_line.push(n);
 			items.len() - 1
 		});
 		let b_id = *to_id.entry(b).or_insert_with(|| {
-			items.push(a);
+			items.push(b);
+			first_line.push(n);
 			items.len() - 1
 		});
-		if let Some(_) = links.insert(b_id, a_id) {
-			return Err("unexpected multiple orbits per object")?;
+		if links.contains_key(&b_id) {
+			problems.push(Problem::TwoCentres { line: n, object: b.to_string(), first: link_line[&b_id] });
+			continue;
 		}
+		links.insert(b_id, a_id);
+		link_line.insert(b_id, n);
+	}
+
+	// Walking towards the centre from everywhere; running into the
+	// current walk again closes a cycle.
+	let mut state = vec![0u8; items.len()];
+	for start in 0..items.len() {
+		let mut walk = Vec::new();
+		let mut id = start;
+		while state[id] == 0 {
+			state[id] = 1;
+			walk.push(id);
+			match links.get(&id) {
+				Some(&center) => id = center,
+				None => break,
+			}
+		}
+		if state[id] == 1 && links.contains_key(&id) {
+			let from = walk.iter().position(|&w| w == id).unwrap();
+			let members = &walk[from..];
+			problems.push(Problem::Cycle {
+				members: members.iter().map(|&m| items[m].to_string()).collect(),
+				lines: members.iter().map(|m| link_line[m]).collect(),
+			});
+		}
+		for w in walk {
+			state[w] = 2;
+		}
+	}
+
+	let roots: Vec<usize> = (0..items.len()).filter(|id| !links.contains_key(id)).collect();
+	if !items.is_empty() && (roots.len() != 1 || items[roots[0]] != "COM") {
+		problems.push(Problem::Roots(roots.iter().map(|&r| (items[r].to_string(), first_line[r])).collect()));
 	}
 
-	Ok(Scheme { items: items, to_id: to_id, links: links })
+	(Scheme { items, to_id, links }, problems)
 }
 
 #[cfg(test)]
@@ -125,7 +435,7 @@ mod tests {
 		let scheme = parse(&INPUT).unwrap();
 		let root_id = *scheme.to_id.get("COM").unwrap();
 		let node_id = *scheme.to_id.get("B").unwrap();
-		let steps = try_reach(&scheme, node_id, root_id);
+		let steps = Tree::from(&scheme).reach(node_id, root_id);
 		assert_eq!(steps, Some(1));
 	}
 
@@ -134,7 +444,7 @@ mod tests {
 		let scheme = parse(&INPUT).unwrap();
 		let to_id = *scheme.to_id.get("I").unwrap();
 		let from_id = *scheme.to_id.get("F").unwrap();
-		let steps = try_reach(&scheme, from_id, to_id);
+		let steps = Tree::from(&scheme).reach(from_id, to_id);
 		assert_eq!(steps, None);
 	}
 	
@@ -162,6 +472,117 @@ mod tests {
 			I)SAN
 		";
 		let scheme = parse(&input).unwrap();
-		assert_eq!(solve_part2(&scheme), 4);
+		assert_eq!(solve_part2(&scheme), Ok(4));
+	}
+
+	#[test]
+	fn tree() {
+		let scheme = parse(&INPUT).unwrap();
+		let tree = Tree::from(&scheme);
+		let id = |name: &str| *scheme.to_id.get(name).unwrap();
+		let ids = |names: &[&str]| names.iter().map(|&n| id(n)).collect::<Vec<_>>();
+
+		assert_eq!(tree.depth[id("L")], 7);
+		assert_eq!(tree.size[id("COM")], 12);
+		assert_eq!(tree.size[id("D")], 7);
+		assert_eq!(tree.size[id("H")], 1);
+		let mut children = ids(&["F", "J"]);
+		children.sort();
+		assert_eq!(tree.children[id("E")], children);
+
+		assert_eq!(tree.lca(id("L"), id("I")), Some(id("D")));
+		assert_eq!(tree.lca(id("H"), id("F")), Some(id("B")));
+		assert_eq!(tree.lca(id("K"), id("E")), Some(id("E")));
+		assert_eq!(tree.lca(id("C"), id("C")), Some(id("C")));
+		assert_eq!(tree.distance(id("L"), id("H")), Some(8));
+
+		assert_eq!(tree.path(id("L"), id("I")), Some(ids(&["L", "K", "J", "E", "D", "I"])));
+		assert_eq!(tree.path(id("COM"), id("C")), Some(ids(&["COM", "B", "C"])));
+		assert_eq!(tree.reach(id("L"), id("D")), Some(4));
+
+		// Apart from the rest.
+		let (scheme, _) = read("COM)A\nX)Y");
+		let tree = Tree::from(&scheme);
+		let id = |name: &str| *scheme.to_id.get(name).unwrap();
+		assert_eq!(tree.lca(id("A"), id("Y")), None);
+		assert_eq!(tree.path(id("A"), id("Y")), None);
+		assert_eq!(tree.reach(id("Y"), id("COM")), None);
+	}
+
+	#[test]
+	fn names() {
+		let scheme = parse(INPUT).unwrap();
+		for (name, &id) in scheme.to_id.iter() {
+			assert_eq!(scheme.items[id], *name);
+		}
+		assert_eq!(solve_part2(&scheme), Err(Problem::Unknown("SAN".to_string())));
+	}
+
+	#[test]
+	fn validation() {
+		let input = "COM)B\nB)C\nC)D\nB)C\nD)E\nE)C\nX)Y\nY)X\nbroken\nA)E\n";
+		let problems = parse(input).err().unwrap().0;
+		assert_eq!(problems, [
+			Problem::Duplicate { line: 4, first: 2 },
+			Problem::TwoCentres { line: 6, object: "C".to_string(), first: 2 },
+			Problem::Syntax { line: 9, text: "broken".to_string() },
+			Problem::TwoCentres { line: 10, object: "E".to_string(), first: 5 },
+			Problem::Cycle { members: vec!["X".to_string(), "Y".to_string()], lines: vec![8, 7] },
+			Problem::Roots(vec![("COM".to_string(), 1), ("A".to_string(), 10)]),
+		]);
+
+		let problems = parse("B)C\nC)D\nD)B\nCOM)E\n").err().unwrap().0;
+		assert_eq!(problems, [
+			Problem::Cycle { members: vec!["B".to_string(), "D".to_string(), "C".to_string()], lines: vec![3, 2, 1] },
+		]);
+		assert_eq!(problems[0].to_string(), "lines 3, 2, 1: cycle through B, D, C");
+
+		let problems = parse("COM)A\nX)Y\nZ)W\n").err().unwrap().0;
+		assert_eq!(problems, [
+			Problem::Roots(vec![("COM".to_string(), 1), ("X".to_string(), 2), ("Z".to_string(), 3)]),
+		]);
+		assert_eq!(problems[0].to_string(), "expected only COM to orbit nothing, got COM (line 1), X (line 2), Z (line 3)");
+
+		let problems = parse("A)B\n").err().unwrap().0;
+		assert_eq!(problems, [Problem::Roots(vec![("A".to_string(), 1)])]);
+	}
+
+	#[test]
+	fn exports() {
+		let input = format!("{}\nK)YOU\nI)SAN\n", INPUT);
+		let scheme = parse(&input).unwrap();
+		let tree = Tree::from(&scheme);
+
+		let ascii = to_ascii(&scheme, &tree, &[]);
+		assert_eq!(ascii, "\
+COM
+`-- B
+    +-- C
+    |   `-- D
+    |       +-- E
+    |       |   +-- F
+    |       |   `-- J
+    |       |       `-- K
+    |       |           +-- L
+    |       |           `-- YOU
+    |       `-- I
+    |           `-- SAN
+    `-- G
+        `-- H
+");
+
+		let path = tree.path(scheme.id("YOU").unwrap(), scheme.id("SAN").unwrap()).unwrap();
+		let ascii = to_ascii(&scheme, &tree, &path);
+		let starred: Vec<&str> = ascii.lines().filter(|l| l.ends_with(" *")).map(|l| l.trim_start_matches(|c| " |+-`".contains(c))).collect();
+		assert_eq!(starred, ["D *", "E *", "J *", "K *", "YOU *", "I *", "SAN *"]);
+
+		let dot = to_dot(&scheme, &tree, &path);
+		assert!(dot.starts_with("digraph orbits {"));
+		assert_eq!(dot.matches(" -> ").count(), 13);
+		assert!(dot.contains("\t\"COM\" -> \"B\";\n"));
+		assert!(dot.contains("\t\"K\" -> \"YOU\" [color=red, penwidth=2];\n"));
+		assert!(dot.contains("\t\"D\" -> \"I\" [color=red, penwidth=2];\n"));
+		assert!(dot.contains("\t\"SAN\" [color=red, fontcolor=red, penwidth=2];\n"));
+		assert!(!dot.contains("\"COM\" [color=red"));
 	}
 }
@@ -1,4 +1,12 @@
 use std::io::{self, Read};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicIsize, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::env;
+use std::fs;
+
+use permute::{Combinations, Permutations};
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Result<T, E = Error> = std::result::Result<T, E>;
@@ -18,21 +26,33 @@ fn main() -> Result<()> {
 	io::stdin().read_to_string(&mut input)?;
 
 	let ram = parse(&input)?;
-	
-	let mut p1 = std::isize::MIN;
-	let mut phases = [0,1,2,3,4];
-	while permute(&mut phases) {
-		p1 = p1.max(evaluate(&phases, &ram));
-	}
 
-	let mut p2 = std::isize::MIN;
-	let mut phases = [5,6,7,8,9];
-	while permute(&mut phases) {
-		p2 = p2.max(evaluate(&phases, &ram));
+	// A circuit description in a file overrides the puzzle.
+	if let Some(path) = env::args().nth(1) {
+		let circuit = parse_circuit(&fs::read_to_string(path)?)?;
+		for (sink, signal) in simulate(&circuit, &ram) {
+			match signal {
+				Some(x) => println!("{}: {}", sink, x),
+				None => println!("{}: no signal", sink),
+			}
+		}
+		return Ok(());
 	}
 	
-	println!("p1: {}", p1);
-	println!("p2: {}", p2);
+	let threads = thread::available_parallelism().map_or(1, |n| n.get());
+	let strategies = [
+		Strategy::Exhaustive,
+		Strategy::RandomRestart { restarts: 16, seed: 7 },
+		Strategy::BranchAndBound { monotonic: false },
+		Strategy::BranchAndBound { monotonic: true },
+	];
+
+	for (name, alphabet) in [("p1", [0,1,2,3,4]), ("p2", [5,6,7,8,9])].iter() {
+		for &strategy in strategies.iter() {
+			let best = search(&ram, alphabet, alphabet.len(), strategy, threads).ok_or("no phases")?;
+			println!("{}: {} (phases {:?}, {} evaluations, {:?})", name, best.signal, best.phases, best.evaluations, strategy);
+		}
+	}
 	
 	Ok(())
 }
@@ -47,40 +67,6 @@ fn parse(input: &str) -> Result<Box<[isize]>> {
 	Ok(ram.into_boxed_slice())
 }
 
-fn permute(data: &mut [u8]) -> bool {
-	// Finding the longest non-increasing suffix.
-	let mut i = data.len() - 1;
-	while i > 0 && data[i - 1] >= data[i] {
-		i -= 1;
-	}
-	
-	if i <= 0 {
-		return false;
-	}
-
-	// data[i - 1] is the pivot, so finding the rightmost
-	// element, which exceeds it.
-	let mut j = data.len() - 1;
-	while data[j] <= data[i - 1] {
-		j -= 1;
-	}
-
-	// data[j] will be the new pivot.
-	debug_assert!(j >= i, "wrong pivot");
-
-	data.swap(i - 1, j);
-
-	// Reversing the suffix.
-	j = data.len() - 1;
-	while i < j {
-		data.swap(i, j);
-		i += 1;
-		j -= 1;
-	}
-	
-	true
-}
-
 fn evaluate(phases: &[u8], code: &[isize]) -> isize {
 	let mut machines: Vec<Machine> = phases.iter().map(|_| Machine::from(&code)).collect();
 
@@ -118,6 +104,475 @@ fn evaluate(phases: &[u8], code: &[isize]) -> isize {
 	signal
 }
 
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum Strategy {
+	// Every arrangement of the alphabet.
+	Exhaustive,
+	// Hill climbing over swaps from random arrangements.
+	RandomRestart { restarts: usize, seed: u64 },
+	// Depth first over phase prefixes, extending a snapshot of the
+	// prefix machines instead of rerunning them. Once every prefix
+	// amplifier has halted the rest only depends on the signal, so a
+	// prefix is cut when a smaller one of the same phase set already
+	// got the same signal. With `monotonic` (amplifiers never turn a
+	// bigger signal into a smaller one) it is also cut when that one
+	// got a higher signal, or when even the best amplifier at every
+	// remaining step could not reach the best signal found so far.
+	// Whatever a cut prefix leads to is matched by a smaller setting or
+	// loses outright, so the winner does not depend on which thread got
+	// there first.
+	BranchAndBound { monotonic: bool },
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+struct Best {
+	signal: isize,
+	phases: Vec<u8>,
+	// Complete circuits evaluated to get here.
+	evaluations: usize,
+}
+
+impl Best {
+	// Higher signal wins, ties go to the smaller phases so the answer
+	// does not depend on how the work was split.
+	fn merge(self, other: Best) -> Best {
+		let evaluations = self.evaluations + other.evaluations;
+		let mut best = if (other.signal, &self.phases) > (self.signal, &other.phases) {
+			other
+		} else {
+			self
+		};
+		best.evaluations = evaluations;
+		best
+	}
+}
+
+// Finds the best setting of `amps` distinct phases from `alphabet`,
+// spreading the work over `threads`.
+fn search(code: &[isize], alphabet: &[u8], amps: usize, strategy: Strategy, threads: usize) -> Option<Best> {
+	if amps == 0 || amps > alphabet.len() || alphabet.len() > 64 {
+		return None;
+	}
+
+	// Work items: the first phase for the tree searches, the restart
+	// number for hill climbing.
+	let items = match strategy {
+		Strategy::RandomRestart { restarts, .. } => restarts,
+		_ => alphabet.len(),
+	};
+
+	let next = AtomicUsize::new(0);
+	let seen = Mutex::new(Seen::default());
+	let record = AtomicIsize::new(isize::MIN);
+
+	let worker = || {
+		let mut best: Option<Best> = None;
+		loop {
+			let i = next.fetch_add(1, Ordering::Relaxed);
+			if i >= items {
+				break;
+			}
+
+			let found = match strategy {
+				Strategy::Exhaustive => {
+					let mut found = None;
+					arrange(&[alphabet[i]], alphabet, amps, &mut |phases| {
+						let candidate = Best { signal: evaluate(phases, code), phases: phases.to_vec(), evaluations: 1 };
+						found = merge(found.take(), candidate);
+					});
+					found
+				},
+				Strategy::RandomRestart { seed, .. } => {
+					let mut rng = Rng::from(seed ^ (i as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+					Some(climb(code, alphabet, amps, &mut rng))
+				},
+				Strategy::BranchAndBound { monotonic } => {
+					let mut bnb = Bnb { code, alphabet, amps, monotonic, seen: &seen, record: &record, best: None };
+					let root = Snapshot { machines: Vec::new(), signal: 0 };
+					bnb.descend(root.extend(code, alphabet[i]), &mut vec![alphabet[i]], 1 << i);
+					bnb.best
+				},
+			};
+			if let Some(found) = found {
+				best = merge(best.take(), found);
+			}
+		}
+		best
+	};
+
+	thread::scope(|s| {
+		let handles: Vec<_> = (0..threads.max(1)).map(|_| s.spawn(worker)).collect();
+		handles.into_iter()
+			.filter_map(|h| h.join().unwrap())
+			.fold(None, merge)
+	})
+}
+
+fn merge(acc: Option<Best>, b: Best) -> Option<Best> {
+	Some(match acc {
+		Some(a) => a.merge(b),
+		None => b,
+	})
+}
+
+// Calls `f` with every extension of `prefix` to `len` distinct symbols:
+// each choice of the missing symbols, in all of their orders.
+fn arrange(prefix: &[u8], alphabet: &[u8], len: usize, f: &mut dyn FnMut(&[u8])) {
+	let rest: Vec<u8> = alphabet.iter().cloned().filter(|x| !prefix.contains(x)).collect();
+	let mut phases = prefix.to_vec();
+	for chosen in Combinations::from(&rest, len - prefix.len()) {
+		for order in Permutations::from(&chosen) {
+			phases.truncate(prefix.len());
+			phases.extend(order);
+			f(&phases);
+		}
+	}
+}
+
+fn climb(code: &[isize], alphabet: &[u8], amps: usize, rng: &mut Rng) -> Best {
+	// Shuffled alphabet: the first `amps` are in use, the rest spare.
+	let mut pool = alphabet.to_vec();
+	for i in (1..pool.len()).rev() {
+		pool.swap(i, rng.below(i + 1));
+	}
+
+	let mut evaluations = 1;
+	let mut signal = evaluate(&pool[..amps], code);
+	loop {
+		let mut step = None;
+		for i in 0..amps {
+			for j in i + 1..pool.len() {
+				pool.swap(i, j);
+				let s = evaluate(&pool[..amps], code);
+				evaluations += 1;
+				pool.swap(i, j);
+				if s > step.map_or(signal, |(best, _, _)| best) {
+					step = Some((s, i, j));
+				}
+			}
+		}
+		match step {
+			Some((s, i, j)) => {
+				pool.swap(i, j);
+				signal = s;
+			},
+			None => break,
+		}
+	}
+
+	Best { signal, phases: pool[..amps].to_vec(), evaluations }
+}
+
+// xorshift64, good enough to pick starting points.
+struct Rng(u64);
+
+impl Rng {
+	fn from(seed: u64) -> Rng {
+		Rng(seed.max(1))
+	}
+
+	fn below(&mut self, n: usize) -> usize {
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 7;
+		self.0 ^= self.0 << 17;
+		(self.0 % n as u64) as usize
+	}
+}
+
+// The amplifiers of a phase prefix after their first pass.
+#[derive(Clone)]
+struct Snapshot {
+	machines: Vec<Machine>,
+	signal: isize,
+}
+
+impl Snapshot {
+	fn extend(&self, code: &[isize], phase: u8) -> Snapshot {
+		let mut next = self.clone();
+		let mut m = Machine::from(code);
+		m.feed(phase as isize);
+		m.feed(next.signal);
+
+		let mut output = Vec::new();
+		let _ = m.run(&mut output);
+		if let Some(s) = output.last() {
+			next.signal = *s;
+		}
+		next.machines.push(m);
+		next
+	}
+
+	fn is_settled(&self) -> bool {
+		self.machines.iter().all(|m| m.is_halted())
+	}
+
+	// Keeps the loop going like `evaluate` does after the first pass.
+	fn finish(mut self) -> isize {
+		let mut output = Vec::new();
+		while self.machines.iter().any(|m| !m.is_halted()) {
+			for m in self.machines.iter_mut().filter(|m| !m.is_halted()) {
+				m.feed(self.signal);
+				output.clear();
+				let _ = m.run(&mut output);
+				if let Some(s) = output.last() {
+					self.signal = *s;
+				}
+			}
+		}
+		self.signal
+	}
+}
+
+// Settled prefixes met so far and their signals, keyed by the set of
+// phases used.
+#[derive(Default)]
+struct Seen(HashMap<u64, Vec<(isize, Vec<u8>)>>);
+
+struct Bnb<'a> {
+	code: &'a [isize],
+	alphabet: &'a [u8],
+	amps: usize,
+	monotonic: bool,
+	seen: &'a Mutex<Seen>,
+	// Best signal any thread has found.
+	record: &'a AtomicIsize,
+	best: Option<Best>,
+}
+
+impl<'a> Bnb<'a> {
+	fn descend(&mut self, snapshot: Snapshot, phases: &mut Vec<u8>, used: u64) {
+		if phases.len() == self.amps {
+			let candidate = Best { signal: snapshot.finish(), phases: phases.clone(), evaluations: 1 };
+			self.record.fetch_max(candidate.signal, Ordering::Relaxed);
+			self.best = merge(self.best.take(), candidate);
+			return;
+		}
+
+		if snapshot.is_settled() {
+			if self.is_dominated(used, snapshot.signal, phases) {
+				return;
+			}
+			let bound = self.bound(snapshot.signal, used, phases.len());
+			if bound.is_some_and(|b| b < self.record.load(Ordering::Relaxed)) {
+				return;
+			}
+		}
+
+		for (i, &x) in self.alphabet.iter().enumerate() {
+			if used & (1 << i) != 0 {
+				continue;
+			}
+			phases.push(x);
+			self.descend(snapshot.extend(self.code, x), phases, used | (1 << i));
+			phases.pop();
+		}
+	}
+
+	// The most a settled prefix can still lead to: each missing
+	// amplifier can do no better than the best of the unused phases on
+	// the bound so far. Only known with `monotonic` and when the unused
+	// phases halt after one pass.
+	fn bound(&self, signal: isize, used: u64, len: usize) -> Option<isize> {
+		if !self.monotonic {
+			return None;
+		}
+
+		let mut bound = signal;
+		for _ in len..self.amps {
+			let root = Snapshot { machines: Vec::new(), signal: bound };
+			let mut next = None;
+			for (i, &x) in self.alphabet.iter().enumerate() {
+				if used & (1 << i) != 0 {
+					continue;
+				}
+				let out = root.extend(self.code, x);
+				if !out.is_settled() {
+					return None;
+				}
+				next = next.max(Some(out.signal));
+			}
+			bound = next?;
+		}
+		Some(bound)
+	}
+
+	// Records the prefix, telling whether a smaller one at least as good
+	// is known.
+	fn is_dominated(&self, used: u64, signal: isize, phases: &[u8]) -> bool {
+		let beats = |s: isize, q: &[u8], signal: isize, phases: &[u8]| {
+			q < phases && (s == signal || self.monotonic && s > signal)
+		};
+
+		let mut seen = self.seen.lock().unwrap();
+		let known = seen.0.entry(used).or_default();
+		if known.iter().any(|(s, q)| beats(*s, q, signal, phases)) {
+			return true;
+		}
+		known.retain(|(s, q)| !beats(signal, phases, *s, q));
+		known.push((signal, phases.to_vec()));
+		false
+	}
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+enum Node {
+	Input,
+	Amp(usize),
+	Sink(usize),
+}
+
+// Amplifiers wired up in an arbitrary graph. Every output is copied to
+// all outgoing edges, an amplifier with several incoming edges takes
+// from them in turns.
+#[derive(PartialEq, Eq, Clone, Debug)]
+struct Circuit {
+	// Names and phases.
+	amps: Vec<(String, isize)>,
+	sinks: Vec<String>,
+	edges: Vec<(Node, Node)>,
+	// Signal the `in` node starts with.
+	input: isize,
+}
+
+// One statement per line, `#` starts a comment:
+//
+//     in = 0          the starting signal, 0 when omitted
+//     A = 4           amplifier A with phase 4
+//     A -> B, C       A's output goes to both B and C
+//
+// Edge targets which are not amplifiers are sinks.
+fn parse_circuit(s: &str) -> Result<Circuit> {
+	let mut amps = Vec::new();
+	let mut input = 0;
+	let mut links = Vec::new();
+
+	for (n, line) in s.lines().enumerate() {
+		let line = line.split('#').next().unwrap().trim();
+		if line.is_empty() {
+			continue;
+		}
+		let err = |msg: &str| -> Error { format!("line {}: {}", n + 1, msg).into() };
+
+		if let Some(i) = line.find("->") {
+			let from = line[..i].trim();
+			let to: Vec<&str> = line[i + 2..]
+				.split(|c: char| c == ',' || c.is_whitespace())
+				.filter(|s| !s.is_empty())
+				.collect();
+			if from.is_empty() || to.is_empty() {
+				return Err(err("edge needs both ends"));
+			}
+			for t in to {
+				links.push((n + 1, from.to_string(), t.to_string()));
+			}
+		} else if let Some(i) = line.find('=') {
+			let name = line[..i].trim();
+			let value = line[i + 1..].trim().parse::<isize>().map_err(|e| err(&e.to_string()))?;
+			if name == "in" {
+				input = value;
+			} else if name.is_empty() || name.contains(char::is_whitespace) {
+				return Err(err("bad amplifier name"));
+			} else if amps.iter().any(|(a, _)| a == name) {
+				return Err(err("amplifier declared twice"));
+			} else {
+				amps.push((name.to_string(), value));
+			}
+		} else {
+			return Err(err("expected `name = phase` or `from -> to`"));
+		}
+	}
+
+	let mut sinks: Vec<String> = Vec::new();
+	let mut edges = Vec::new();
+	for (n, from, to) in links {
+		let from = match amps.iter().position(|(a, _)| *a == from) {
+			Some(i) => Node::Amp(i),
+			None if from == "in" => Node::Input,
+			None => return Err(format!("line {}: unknown amplifier {}", n, from).into()),
+		};
+		let to = match amps.iter().position(|(a, _)| *a == to) {
+			Some(i) => Node::Amp(i),
+			None if to == "in" => return Err(format!("line {}: nothing can feed `in`", n).into()),
+			None => match sinks.iter().position(|s| *s == to) {
+				Some(i) => Node::Sink(i),
+				None => {
+					sinks.push(to);
+					Node::Sink(sinks.len() - 1)
+				},
+			},
+		};
+		edges.push((from, to));
+	}
+
+	Ok(Circuit { amps, sinks, edges, input })
+}
+
+// Runs every amplifier until all of them halt or wait for a signal
+// which never comes, returns the last signal each sink got.
+fn simulate(circuit: &Circuit, code: &[isize]) -> Vec<(String, Option<isize>)> {
+	let mut machines: Vec<Machine> = circuit.amps.iter().map(|&(_, phase)| {
+		let mut m = Machine::from(code);
+		m.feed(phase);
+		m
+	}).collect();
+	let mut started = vec![false; machines.len()];
+	let mut queues = vec![VecDeque::new(); circuit.edges.len()];
+	let mut sinks = vec![None; circuit.sinks.len()];
+
+	let mut incoming = HashMap::new();
+	let mut outgoing = HashMap::new();
+	for (e, &(from, to)) in circuit.edges.iter().enumerate() {
+		outgoing.entry(from).or_insert_with(Vec::new).push(e);
+		incoming.entry(to).or_insert_with(Vec::new).push(e);
+	}
+	let mut turns = vec![0; machines.len()];
+
+	let mut send = |from: Node, x: isize, queues: &mut Vec<VecDeque<isize>>| {
+		for &e in outgoing.get(&from).map(|v| &v[..]).unwrap_or(&[]) {
+			match circuit.edges[e].1 {
+				Node::Sink(s) => sinks[s] = Some(x),
+				_ => queues[e].push_back(x),
+			}
+		}
+	};
+	send(Node::Input, circuit.input, &mut queues);
+
+	let mut output = Vec::new();
+	let mut progress = true;
+	while progress {
+		progress = false;
+		for (i, m) in machines.iter_mut().enumerate() {
+			if m.is_halted() {
+				continue;
+			}
+			if started[i] {
+				let edges = incoming.get(&Node::Amp(i)).map(|v| &v[..]).unwrap_or(&[]);
+				let next = (0..edges.len())
+					.map(|k| (turns[i] + k) % edges.len())
+					.find(|&k| !queues[edges[k]].is_empty());
+				match next {
+					Some(k) => {
+						m.feed(queues[edges[k]].pop_front().unwrap());
+						turns[i] = k + 1;
+					},
+					None => continue,
+				}
+			}
+			started[i] = true;
+			progress = true;
+
+			output.clear();
+			let _ = m.run(&mut output);
+			for &x in output.iter() {
+				send(Node::Amp(i), x, &mut queues);
+			}
+		}
+	}
+
+	circuit.sinks.iter().cloned().zip(sinks).collect()
+}
+
+#[derive(Clone)]
 struct Machine {
 	ram: Box<[isize]>,
 	pc: usize,
@@ -350,21 +805,22 @@ fn decode_param(mode: u8, value: isize) -> Param {
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use permute::next_permutation;
 
 	#[test]
 	fn permutations() {
 		let mut data = [0, 1, 2];
-		assert_eq!(permute(&mut data), true);
+		assert!(next_permutation(&mut data));
 		assert_eq!(data, [0, 2, 1]);
-		assert_eq!(permute(&mut data), true);
+		assert!(next_permutation(&mut data));
 		assert_eq!(data, [1, 0, 2]);
-		assert_eq!(permute(&mut data), true);
+		assert!(next_permutation(&mut data));
 		assert_eq!(data, [1, 2, 0]);
-		assert_eq!(permute(&mut data), true);
+		assert!(next_permutation(&mut data));
 		assert_eq!(data, [2, 0, 1]);
-		assert_eq!(permute(&mut data), true);
+		assert!(next_permutation(&mut data));
 		assert_eq!(data, [2, 1, 0]);
-		assert_eq!(permute(&mut data), false);
+		assert!(!next_permutation(&mut data));
 		assert_eq!(data, [2, 1, 0]);
 	}
 	
@@ -398,8 +854,143 @@ mod tests {
 		assert_eq!(run(&[9,7,8,5,6], "3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,54,1105,1,12,1,53,54,53,1008,54,0,55,1001,55,1,55,2,53,55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10"), 18216);
 	}
 
-	fn run(phases: &[u8], code: &str) -> isize {
+	// The puzzle's wiring: a chain of amplifiers with the last one fed
+	// back into the first.
+	fn ring(phases: &[u8], feedback: bool) -> Circuit {
+		let n = phases.len();
+		let amps = phases.iter().enumerate().map(|(i, &p)| (((b'A' + i as u8) as char).to_string(), p as isize)).collect();
+		let mut edges = vec![(Node::Input, Node::Amp(0))];
+		for i in 1..n {
+			edges.push((Node::Amp(i - 1), Node::Amp(i)));
+		}
+		if feedback {
+			edges.push((Node::Amp(n - 1), Node::Amp(0)));
+		}
+		edges.push((Node::Amp(n - 1), Node::Sink(0)));
+
+		Circuit {
+			amps,
+			sinks: vec!["thrusters".to_string()],
+			edges,
+			input: 0,
+		}
+	}
+
+	#[test]
+	fn searches() {
+		let rom = parse("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0").unwrap();
+		let strategies = [
+			Strategy::Exhaustive,
+			Strategy::RandomRestart { restarts: 8, seed: 1 },
+			Strategy::BranchAndBound { monotonic: false },
+			Strategy::BranchAndBound { monotonic: true },
+		];
+		for &strategy in strategies.iter() {
+			for &threads in [1, 3].iter() {
+				let best = search(&rom, &[0,1,2,3,4], 5, strategy, threads).unwrap();
+				assert_eq!((best.signal, best.phases), (43210, vec![4,3,2,1,0]), "{:?}", strategy);
+			}
+		}
+
+		// Fewer amplifiers than phases, cross-checked against brute force.
+		let rom = parse("3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0").unwrap();
+		let mut expected = None;
+		arrange(&[], &[1,3,5,7], 3, &mut |phases| {
+			expected = merge(expected.take(), Best { signal: evaluate(phases, &rom), phases: phases.to_vec(), evaluations: 1 });
+		});
+		let expected = expected.unwrap();
+		assert_eq!(expected.evaluations, 24);
+		for &strategy in strategies[..3].iter() {
+			let best = search(&rom, &[1,3,5,7], 3, strategy, 2).unwrap();
+			assert_eq!((best.signal, &best.phases), (expected.signal, &expected.phases), "{:?}", strategy);
+		}
+
+		let rom = parse("3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5").unwrap();
+		let best = search(&rom, &[5,6,7,8,9], 5, Strategy::BranchAndBound { monotonic: false }, 4).unwrap();
+		assert_eq!((best.signal, best.phases), (139629729, vec![9,8,7,6,5]));
+
+		assert_eq!(search(&rom, &[5,6], 3, Strategy::Exhaustive, 1), None);
+
+		// Adds its phase to the signal and halts: every order of a phase
+		// set ties, so the smallest must win however the work is split,
+		// and prefixes of an already seen set get cut.
+		let rom = parse("3,11,3,12,1,11,12,11,4,11,99,0,0").unwrap();
+		for &strategy in strategies[2..].iter() {
+			for threads in 1..=4 {
+				let best = search(&rom, &[0,1,2,3,4], 5, strategy, threads).unwrap();
+				assert_eq!((best.signal, &best.phases[..]), (10, &[0,1,2,3,4][..]), "{:?} {}", strategy, threads);
+				assert!(best.evaluations < 120, "{:?} {}", strategy, threads);
+			}
+		}
+		let best = search(&rom, &[0,1,2,3,4], 5, Strategy::BranchAndBound { monotonic: false }, 1).unwrap();
+		assert_eq!(best.evaluations, 5);
+
+		// Every order gives a different signal, so only the bound cuts.
+		// Tried in this order the first setting is the best one, and no
+		// other prefix can catch up with it.
+		let rom = parse("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0").unwrap();
+		for &monotonic in [false, true].iter() {
+			let best = search(&rom, &[4,3,2,1,0], 5, Strategy::BranchAndBound { monotonic }, 1).unwrap();
+			assert_eq!((best.signal, &best.phases[..]), (43210, &[4,3,2,1,0][..]));
+			assert_eq!(best.evaluations, if monotonic { 1 } else { 120 });
+		}
+	}
+
+	#[test]
+	fn circuits() {
+		let code = "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
 		let rom = parse(code).unwrap();
-		evaluate(phases, &rom)
+		let expected = vec![("thrusters".to_string(), Some(139629729))];
+		assert_eq!(simulate(&ring(&[9,8,7,6,5], true), &rom), expected);
+
+		let config = "\
+# the puzzle ring, written out
+A = 9
+B = 8
+C = 7
+D = 6
+E = 5
+in -> A
+A -> B
+B -> C
+C -> D
+D -> E
+E -> A, thrusters
+";
+		let circuit = parse_circuit(config).unwrap();
+		assert_eq!(circuit, ring(&[9,8,7,6,5], true));
+		assert_eq!(simulate(&circuit, &rom), expected);
+
+		let rom = parse("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0").unwrap();
+		assert_eq!(simulate(&ring(&[4,3,2,1,0], false), &rom), vec![("thrusters".to_string(), Some(43210))]);
+	}
+
+	#[test]
+	fn fan_out_and_in() {
+		// Adds its phase to every signal it gets.
+		let rom = parse("3,13,3,14,1,13,14,15,4,15,1105,1,2,0,0,0").unwrap();
+		let config = "\
+in = 1
+A = 10
+B = 100
+C = 1000
+in -> A
+A -> B, C, left
+B -> C
+C -> right
+";
+		let circuit = parse_circuit(config).unwrap();
+		assert_eq!(circuit.sinks, ["left", "right"]);
+		// C sees 11 from A, then 111 from B.
+		assert_eq!(simulate(&circuit, &rom), vec![
+			("left".to_string(), Some(11)),
+			("right".to_string(), Some(1111)),
+		]);
+
+		assert!(parse_circuit("A = 1\nA = 2").is_err());
+		assert!(parse_circuit("A -> B").is_err());
+		assert!(parse_circuit("A = 1\nA -> in").is_err());
+		assert!(parse_circuit("A = x").is_err());
+		assert!(parse_circuit("A").is_err());
 	}
 }
@@ -1,66 +1,340 @@
 use std::io::{self, Read};
+use std::env;
+use std::fmt;
+use std::fs;
+
+use bitmap::{ocr, Bitmap};
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Result<T, E = Error> = std::result::Result<T, E>;
 
 fn main() -> Result<()> {
+	let mut args = env::args().skip(1).peekable();
+	if args.peek().map(String::as_str) == Some("import") {
+		args.next();
+		return import(args);
+	}
+
 	let mut input = String::new();
 	io::stdin().read_to_string(&mut input)?;
 
-	let w = 25;
-	let h = 6;
-	let data = input.trim().as_bytes();
+	// Options pick the size, what is see-through and how to colour it,
+	// every other argument is a file to export the image to, by extension.
+	let mut w = 25;
+	let mut h = 6;
+	let mut clear = vec![2];
+	let mut background = 2;
+	let mut palette = Palette::default();
+	let mut layer = None;
+	let mut paths = Vec::new();
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--size" => {
+				let size = args.next().ok_or("missing size")?;
+				let (a, b) = size.split_once('x').ok_or("size is WxH")?;
+				w = a.parse()?;
+				h = b.parse()?;
+			},
+			"--clear" => {
+				let digits = args.next().ok_or("missing digits")?;
+				clear = digits.split(',').map(|d| d.parse()).collect::<Result<Vec<u8>, _>>()?;
+			},
+			"--background" => background = args.next().ok_or("missing digit")?.parse()?,
+			"--palette" => {
+				for entry in args.next().ok_or("missing palette")?.split(',') {
+					palette.set(entry)?;
+				}
+			},
+			"--layer" => layer = Some(args.next().ok_or("missing layer")?.parse::<usize>()?),
+			_ => paths.push(arg),
+		}
+	}
 
-	let (ones, twos) = solve_part1(&data, w * h).ok_or("failed to parse layers")?;
+	let rule = Transparency::clear(&clear, background)?;
+	let sif = SifImage::decode(input.trim().as_bytes(), w, h)?;
+	let (ones, twos) = solve_part1(&sif).ok_or("no layers")?;
 	println!("p1: {}", ones * twos);
 
-	let buf = flatten(&data, w, h);
-	println!("p2:");
-	paint(&buf, w);
+	let flat = sif.composite(&rule);
+	let shown = match layer {
+		Some(i) => sif.layers().nth(i).ok_or_else(|| format!("there is no layer {}", i))?,
+		None => flat.layers().next().unwrap(),
+	};
+	let image = flat.layers().next().unwrap().to_bitmap();
+	image.dump();
+
+	for path in paths {
+		if path.ends_with(".png") {
+			fs::write(&path, image.scaled(8).to_png())?;
+		} else if path.ends_with(".pbm") {
+			fs::write(&path, image.to_pbm())?;
+		} else if path.ends_with(".pgm") {
+			fs::write(&path, shown.to_pgm(&palette))?;
+		} else if path.ends_with(".ppm") {
+			fs::write(&path, shown.to_ppm(&palette))?;
+		} else {
+			return Err(format!("unsupported image format: {}", path).into());
+		}
+	}
+
+	println!("p2: {}", ocr(&image)?);
 	
 	Ok(())
 }
 
-fn solve_part1(data: &[u8], layer_size: usize) -> Option<(usize, usize)> {
-	let (_, (c, _)) = data
-		.chunks_exact(layer_size)
-		.map(|c| (c, c.iter().fold(0, |acc, &x| if x == b'0' { acc + 1 } else { acc })))
-		.enumerate()
-		.min_by_key(|&x| (x.1).1)?;
+// Turns a monochrome PBM into a SIF digit stream that flattens back to
+// it, hidden under the given number of transparent layers.
+fn import(mut args: impl Iterator<Item = String>) -> Result<()> {
+	let path = args.next().ok_or("usage: import <file.pbm> [layers] [seed]")?;
+	let depth: usize = args.next().map_or(Ok(0), |s| s.parse())?;
+	let seed = args.next().map_or(Ok(1), |s| s.parse())?;
 
-	Some(c.iter().fold((0, 0), |acc, &x| {
-		match x {
-			b'1' => (acc.0 + 1, acc.1),
-			b'2' => (acc.0, acc.1 + 1),
-			_ => acc,
+	let bitmap = Bitmap::from_pbm(&fs::read(&path)?)?;
+	if bitmap.w == 0 || bitmap.h == 0 {
+		return Err(SifError::ZeroSize.into());
+	}
+	if depth.checked_add(1).and_then(|n| n.checked_mul(bitmap.pixels.len())).is_none() {
+		return Err(format!("{} layers are too many", depth).into());
+	}
+	let sif = SifImage::conceal(&bitmap, depth, seed);
+	eprintln!("{}x{}, {} layers", sif.w, sif.h, sif.layers().count());
+	println!("{}", sif.encode());
+	Ok(())
+}
+
+// The layer with the fewest zeros, its count of ones and of twos.
+fn solve_part1(image: &SifImage) -> Option<(usize, usize)> {
+	let counts = image.layers()
+		.map(|l| l.histogram())
+		.min_by_key(|c| c[0])?;
+
+	Some((counts[1], counts[2]))
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum SifError {
+	Empty,
+	ZeroSize,
+	TooLarge { w: usize, h: usize },
+	NotDigit { offset: usize, byte: u8 },
+	// The data ends partway into a layer.
+	Partial { len: usize, layer_size: usize },
+	// Only 0 to 9 are colours.
+	BadDigit(u8),
+}
+
+impl fmt::Display for SifError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			SifError::Empty => write!(f, "no image data"),
+			SifError::ZeroSize => write!(f, "image has no pixels"),
+			SifError::TooLarge { w, h } => write!(f, "{}x{} is too large", w, h),
+			SifError::NotDigit { offset, byte } => write!(f, "{:?} at {} is not a digit", byte as char, offset),
+			SifError::Partial { len, layer_size } => {
+				write!(f, "{} digits are not whole layers of {}", len, layer_size)
+			},
+			SifError::BadDigit(d) => write!(f, "{} is not a digit", d),
 		}
-	}))
+	}
 }
 
-fn flatten(data: &[u8], w: usize, h: usize) -> Box<[u8]> {
-	let layer_size = w * h;
+impl std::error::Error for SifError {}
 
-	let chunks = data.chunks_exact(layer_size);
-	assert!(chunks.remainder().is_empty());
+// A Space Image Format picture: layers of w * h digits, the first one
+// on top.
+#[derive(PartialEq, Eq, Clone, Debug)]
+struct SifImage {
+	w: usize,
+	h: usize,
+	// Digit values, layer after layer, row after row.
+	pixels: Vec<u8>,
+}
 
-	let mut buf = vec![2; layer_size];
-	for c in chunks.rev() {
-		for i in 0..layer_size {
-			if c[i] != b'2' {
-				buf[i] = c[i];
-			}
+// One layer of a `SifImage`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+struct Layer<'a> {
+	w: usize,
+	h: usize,
+	pixels: &'a [u8],
+}
+
+impl<'a> Layer<'a> {
+	fn get(&self, x: usize, y: usize) -> u8 {
+		self.pixels[y * self.w + x]
+	}
+
+	// How many pixels there are of each digit.
+	fn histogram(&self) -> [usize; 10] {
+		let mut counts = [0; 10];
+		for &x in self.pixels {
+			counts[x as usize] += 1;
+		}
+		counts
+	}
+
+	// Ones are lit, as the puzzle's message is written in them.
+	fn to_bitmap(self) -> Bitmap {
+		Bitmap { w: self.w, h: self.h, pixels: self.pixels.iter().map(|&x| x == 1).collect() }
+	}
+
+	// Plain PGM, each digit in the grey of its colour.
+	fn to_pgm(self, palette: &Palette) -> String {
+		let mut s = format!("P2\n{} {}\n255\n", self.w, self.h);
+		for row in self.pixels.chunks_exact(self.w) {
+			let row: Vec<String> = row.iter().map(|&x| palette.grey(x).to_string()).collect();
+			s.push_str(&row.join(" "));
+			s.push('\n');
+		}
+		s
+	}
+
+	// Plain PPM.
+	fn to_ppm(self, palette: &Palette) -> String {
+		let mut s = format!("P3\n{} {}\n255\n", self.w, self.h);
+		for row in self.pixels.chunks_exact(self.w) {
+			let row: Vec<String> = row.iter()
+				.map(|&x| palette.0[x as usize])
+				.map(|[r, g, b]| format!("{} {} {}", r, g, b))
+				.collect();
+			s.push_str(&row.join("  "));
+			s.push('\n');
+		}
+		s
+	}
+}
+
+// The colour of each digit.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+struct Palette([[u8; 3]; 10]);
+
+impl Palette {
+	// Takes a "D=RRGGBB" entry.
+	fn set(&mut self, entry: &str) -> Result<()> {
+		let (digit, rgb) = entry.split_once('=').ok_or("palette entry is D=RRGGBB")?;
+		let digit: usize = digit.parse()?;
+		if digit > 9 || rgb.len() != 6 {
+			return Err(format!("bad palette entry: {}", entry).into());
+		}
+		let rgb = u32::from_str_radix(rgb, 16)?;
+		self.0[digit] = [(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8];
+		Ok(())
+	}
+
+	fn grey(&self, digit: u8) -> u8 {
+		let [r, g, b] = self.0[digit as usize];
+		((299 * r as u32 + 587 * g as u32 + 114 * b as u32) / 1000) as u8
+	}
+}
+
+// Black, white and magenta for what is transparent, then shades of grey.
+impl Default for Palette {
+	fn default() -> Palette {
+		let mut colours = [[0; 3]; 10];
+		colours[1] = [255, 255, 255];
+		colours[2] = [255, 0, 255];
+		for (d, c) in colours.iter_mut().enumerate().skip(3) {
+			let v = (d * 255 / 10) as u8;
+			*c = [v, v, v];
+		}
+		Palette(colours)
+	}
+}
+
+// Which digits let the layers below show through, and what is left
+// where every layer does.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+struct Transparency {
+	clear: [bool; 10],
+	background: u8,
+}
+
+impl Transparency {
+	fn clear(digits: &[u8], background: u8) -> Result<Transparency, SifError> {
+		if background > 9 {
+			return Err(SifError::BadDigit(background));
+		}
+		let mut clear = [false; 10];
+		for &d in digits {
+			*clear.get_mut(d as usize).ok_or(SifError::BadDigit(d))? = true;
 		}
+		Ok(Transparency { clear, background })
 	}
+}
 
-	buf.into_boxed_slice()
+// The puzzle's: 2 is transparent.
+impl Default for Transparency {
+	fn default() -> Transparency {
+		Transparency::clear(&[2], 2).unwrap()
+	}
 }
 
-fn paint(data: &[u8], w: usize) {
-	for row in data.chunks_exact(w) {
-		for &x in row {
-			print!("{}", if x == b'0' { ' ' } else { '█' });
+impl SifImage {
+	fn decode(data: &[u8], w: usize, h: usize) -> Result<SifImage, SifError> {
+		if w == 0 || h == 0 {
+			return Err(SifError::ZeroSize);
 		}
-		println!();
+		let layer_size = w.checked_mul(h).ok_or(SifError::TooLarge { w, h })?;
+		if data.is_empty() {
+			return Err(SifError::Empty);
+		}
+		if !data.len().is_multiple_of(layer_size) {
+			return Err(SifError::Partial { len: data.len(), layer_size });
+		}
+
+		let pixels = data.iter().enumerate().map(|(offset, &byte)| match byte {
+			b'0'..=b'9' => Ok(byte - b'0'),
+			_ => Err(SifError::NotDigit { offset, byte }),
+		}).collect::<Result<Vec<_>, _>>()?;
+
+		Ok(SifImage { w, h, pixels })
+	}
+
+	fn encode(&self) -> String {
+		self.pixels.iter().map(|&x| (b'0' + x) as char).collect()
+	}
+
+	fn layers(&self) -> impl Iterator<Item = Layer<'_>> + '_ {
+		self.pixels.chunks_exact(self.w * self.h).map(move |pixels| Layer { w: self.w, h: self.h, pixels })
+	}
+
+	// A single layer image of what shows through from the top.
+	fn composite(&self, rule: &Transparency) -> SifImage {
+		let mut pixels = Vec::with_capacity(self.w * self.h);
+		for y in 0..self.h {
+			for x in 0..self.w {
+				let shown = self.layers().map(|l| l.get(x, y)).find(|&p| !rule.clear[p as usize]);
+				pixels.push(shown.unwrap_or(rule.background));
+			}
+		}
+		SifImage { w: self.w, h: self.h, pixels }
+	}
+
+	// An image of depth + 1 layers that flattens to the bitmap, lit as 1.
+	// Each pixel shows at a random layer, below all 2s, over noise.
+	fn conceal(bitmap: &Bitmap, depth: usize, seed: u64) -> SifImage {
+		let size = bitmap.w * bitmap.h;
+		let mut rng = Rng(seed.max(1));
+		let mut pixels = vec![2; size * (depth + 1)];
+		for (i, &lit) in bitmap.pixels.iter().enumerate() {
+			let shown = rng.below(depth + 1);
+			pixels[shown * size + i] = lit as u8;
+			for layer in shown + 1..=depth {
+				pixels[layer * size + i] = rng.below(3) as u8;
+			}
+		}
+		SifImage { w: bitmap.w, h: bitmap.h, pixels }
+	}
+}
+
+// xorshift64, enough to scatter pixels across layers.
+struct Rng(u64);
+
+impl Rng {
+	fn below(&mut self, n: usize) -> usize {
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 7;
+		self.0 ^= self.0 << 17;
+		(self.0 % n as u64) as usize
 	}
 }
 
@@ -68,10 +342,129 @@ fn paint(data: &[u8], w: usize) {
 mod tests {
 	use super::*;
 
+	fn to_bitmap(data: &[u8], w: usize) -> Bitmap {
+		Bitmap {
+			w,
+			h: data.len() / w,
+			pixels: data.iter().map(|&x| x != b'0').collect(),
+		}
+	}
+
+	// The puzzle's compositing, as the digits it shows.
+	fn flatten(data: &[u8], w: usize, h: usize) -> Result<Box<[u8]>, SifError> {
+		let image = SifImage::decode(data, w, h)?;
+		Ok(image.composite(&Transparency::default()).encode().into_bytes().into_boxed_slice())
+	}
+
 	#[test]
 	fn example2() {
 		let data = "0222112222120000".as_bytes();
-		let f = flatten(&data, 2, 2);
+		let f = flatten(&data, 2, 2).unwrap();
 		assert_eq!(&f[..], &[b'0',b'1',b'1',b'0']);
 	}
+
+	#[test]
+	fn reading() {
+		// A transparent layer over "HI" where the I is a single column.
+		let top = "2".repeat(54);
+		let bottom = "\
+100101000\
+100101000\
+111101000\
+100101000\
+100101000\
+100101000";
+		let data = format!("{}{}", top, bottom);
+		let f = flatten(data.as_bytes(), 9, 6).unwrap();
+		assert!(ocr(&to_bitmap(&f, 9)).is_err());
+
+		let bottom = bottom.replace("101000", "100000");
+		let data = format!("{}{}", top, bottom);
+		let f = flatten(data.as_bytes(), 9, 6).unwrap();
+		let image = to_bitmap(&f, 9);
+		assert_eq!(ocr(&image).unwrap(), "H");
+		assert_eq!(image.to_pbm().lines().nth(1), Some("9 6"));
+	}
+
+	#[test]
+	fn codec() {
+		let sif = SifImage::decode(b"123456789012", 3, 2).unwrap();
+		assert_eq!(sif.layers().count(), 2);
+		let top = sif.layers().next().unwrap();
+		assert_eq!((top.w, top.h, top.get(2, 1)), (3, 2, 6));
+		assert_eq!(top.histogram(), [0, 1, 1, 1, 1, 1, 1, 0, 0, 0]);
+		assert_eq!(solve_part1(&sif), Some((1, 1)));
+		assert_eq!(sif.encode(), "123456789012");
+
+		assert_eq!(SifImage::decode(b"12345", 3, 2), Err(SifError::Partial { len: 5, layer_size: 6 }));
+		assert_eq!(SifImage::decode(b"", 3, 2), Err(SifError::Empty));
+		assert_eq!(SifImage::decode(b"12", 0, 2), Err(SifError::ZeroSize));
+		assert_eq!(SifImage::decode(b"12x4", 2, 1), Err(SifError::NotDigit { offset: 2, byte: b'x' }));
+		assert!(flatten(b"0222112222120", 2, 2).is_err());
+
+		// Zero as the see-through one, over a black background.
+		let sif = SifImage::decode(b"0201" as &[u8], 2, 1).unwrap();
+		let rule = Transparency::clear(&[0], 1).unwrap();
+		assert_eq!(sif.composite(&rule).encode(), "12");
+		let rule = Transparency::clear(&[0, 2], 7).unwrap();
+		assert_eq!(sif.composite(&rule).encode(), "71");
+		assert_eq!(Transparency::clear(&[10], 0), Err(SifError::BadDigit(10)));
+		assert_eq!(Transparency::clear(&[2], 250), Err(SifError::BadDigit(250)));
+
+		// Only ones are lit, whatever the background.
+		let rule = Transparency::clear(&[2], 7).unwrap();
+		let flat = SifImage::decode(b"2102", 4, 1).unwrap().composite(&rule);
+		assert_eq!(flat.encode(), "7107");
+		assert_eq!(flat.layers().next().unwrap().to_bitmap().pixels, [false, true, false, false]);
+		assert_eq!(sif.composite(&Transparency::default()).encode(), "01");
+	}
+
+	#[test]
+	fn exports() {
+		let sif = SifImage::decode(b"0122", 2, 1).unwrap();
+		let mut layers = sif.layers();
+		let top = layers.next().unwrap();
+		let mut palette = Palette::default();
+		assert_eq!(top.to_pgm(&palette), "P2\n2 1\n255\n0 255\n");
+		assert_eq!(layers.next().unwrap().to_ppm(&palette), "P3\n2 1\n255\n255 0 255  255 0 255\n");
+
+		palette.set("1=ff8000").unwrap();
+		assert_eq!(top.to_ppm(&palette), "P3\n2 1\n255\n0 0 0  255 128 0\n");
+		assert_eq!(palette.grey(1), 151);
+		assert!(palette.set("10=000000").is_err());
+		assert!(palette.set("1=fff").is_err());
+	}
+
+	#[test]
+	fn imports() {
+		let plain = b"P1\n# HI\n9 6\n1 0 0 1 0 0 0 0 0\n100100100\n111100100\n100100100\n100100100\n100100100\n";
+		let bitmap = Bitmap::from_pbm(plain).unwrap();
+		assert_eq!((bitmap.w, bitmap.h), (9, 6));
+		assert_eq!(Bitmap::from_pbm(bitmap.to_pbm().as_bytes()).unwrap(), bitmap);
+		assert_eq!(ocr(&bitmap).unwrap_err().to_string(), "unknown glyph at column 6");
+
+		let mut raw = b"P4 9 6\n".to_vec();
+		for row in bitmap.pixels.chunks_exact(9) {
+			let bits = row.iter().fold(0u16, |acc, &x| acc << 1 | x as u16) << 7;
+			raw.extend_from_slice(&bits.to_be_bytes());
+		}
+		assert_eq!(Bitmap::from_pbm(&raw).unwrap(), bitmap);
+		assert!(Bitmap::from_pbm(&raw[..raw.len() - 1]).is_err());
+		assert!(Bitmap::from_pbm(b"P1 2 2 1 0 1").is_err());
+		assert!(Bitmap::from_pbm(b"P2 2 2 255").is_err());
+		assert!(Bitmap::from_pbm(b"P1 99999999999 99999999999\n0 1").is_err());
+		assert!(Bitmap::from_pbm(b"P4 99999999999 99999999999\n\0").is_err());
+		let huge = usize::MAX / 2;
+		assert_eq!(SifImage::decode(b"0", huge, 3), Err(SifError::TooLarge { w: huge, h: 3 }));
+
+		for depth in 0..4 {
+			let sif = SifImage::conceal(&bitmap, depth, 7);
+			assert_eq!(sif.layers().count(), depth + 1);
+			let again = SifImage::decode(sif.encode().as_bytes(), 9, 6).unwrap();
+			let flat = flatten(again.encode().as_bytes(), 9, 6).unwrap();
+			assert_eq!(to_bitmap(&flat, 9), bitmap);
+		}
+		let deep = SifImage::conceal(&bitmap, 5, 7);
+		assert!(deep.layers().next().unwrap().histogram()[2] > 0);
+	}
 }
@@ -1,4 +1,9 @@
 use std::io::{self, Read};
+use std::collections::{BTreeMap, VecDeque};
+use std::env;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Result<T, E = Error> = std::result::Result<T, E>;
@@ -18,6 +23,21 @@ fn main() -> Result<()> {
 	io::stdin().read_to_string(&mut input)?;
 
 	let rom = parse(&input)?;
+
+	// Looking for inputs instead: `reach <pc>` or `output <value>`.
+	let args: Vec<String> = env::args().skip(1).collect();
+	if let [what, x] = &args[..] {
+		let goal = match what.as_str() {
+			"reach" => Goal::Reach(x.parse()?),
+			"output" => Goal::Output(x.parse()?),
+			_ => return Err(format!("unknown goal {}", what).into()),
+		};
+		match explore(&rom, goal, &Limits::default()) {
+			Some(inputs) => println!("{:?}: {:?}", goal, inputs),
+			None => println!("{:?}: nothing found within limits", goal),
+		}
+		return Ok(());
+	}
 	
 	println!("p1: {:?}", execute(&rom, 1)?);
 	println!("p2: {:?}", execute(&rom, 2)?);
@@ -187,6 +207,582 @@ impl Machine {
 	}
 }
 
+type Sym = Rc<Expr>;
+
+// A value computed from the inputs; the k-th value read is Input(k).
+#[derive(PartialEq, Eq, Debug)]
+enum Expr {
+	Const(isize),
+	Input(usize),
+	Add(Sym, Sym),
+	Mul(Sym, Sym),
+	Less(Sym, Sym),
+	Equal(Sym, Sym),
+}
+
+impl Expr {
+	fn constant(&self) -> Option<isize> {
+		match self {
+			Expr::Const(x) => Some(*x),
+			_ => None,
+		}
+	}
+
+	fn eval(&self, model: &[isize]) -> Option<isize> {
+		Some(match self {
+			Expr::Const(x) => *x,
+			Expr::Input(k) => *model.get(*k)?,
+			Expr::Add(a, b) => a.eval(model)?.checked_add(b.eval(model)?)?,
+			Expr::Mul(a, b) => a.eval(model)?.checked_mul(b.eval(model)?)?,
+			Expr::Less(a, b) => (a.eval(model)? < b.eval(model)?) as isize,
+			Expr::Equal(a, b) => (a.eval(model)? == b.eval(model)?) as isize,
+		})
+	}
+
+	fn is_boolean(&self) -> bool {
+		match self {
+			Expr::Less(_, _) | Expr::Equal(_, _) => true,
+			Expr::Const(x) => *x == 0 || *x == 1,
+			_ => false,
+		}
+	}
+
+	// The expression as a sum of inputs times constants, if it is one.
+	fn linear(&self) -> Option<Linear> {
+		match self {
+			Expr::Const(x) => Some(Linear { terms: BTreeMap::new(), constant: *x as i128 }),
+			Expr::Input(k) => {
+				let mut terms = BTreeMap::new();
+				terms.insert(*k, 1);
+				Some(Linear { terms, constant: 0 })
+			},
+			Expr::Add(a, b) => Some(a.linear()?.add(&b.linear()?, 1)),
+			Expr::Mul(a, b) => match (a.constant(), b.constant()) {
+				(Some(x), _) => Some(b.linear()?.scale(x as i128)),
+				(_, Some(x)) => Some(a.linear()?.scale(x as i128)),
+				_ => None,
+			},
+			_ => None,
+		}
+	}
+}
+
+impl fmt::Display for Expr {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Expr::Const(x) => write!(f, "{}", x),
+			Expr::Input(k) => write!(f, "in{}", k),
+			Expr::Add(a, b) => write!(f, "({} + {})", a, b),
+			Expr::Mul(a, b) => write!(f, "{} * {}", a, b),
+			Expr::Less(a, b) => write!(f, "({} < {})", a, b),
+			Expr::Equal(a, b) => write!(f, "({} == {})", a, b),
+		}
+	}
+}
+
+// Builders folding whatever is already known.
+fn add(a: Sym, b: Sym) -> Sym {
+	match (a.constant(), b.constant()) {
+		(Some(x), Some(y)) => Rc::new(Expr::Const(x.wrapping_add(y))),
+		(Some(0), _) => b,
+		(_, Some(0)) => a,
+		_ => Rc::new(Expr::Add(a, b)),
+	}
+}
+
+fn mul(a: Sym, b: Sym) -> Sym {
+	match (a.constant(), b.constant()) {
+		(Some(x), Some(y)) => Rc::new(Expr::Const(x.wrapping_mul(y))),
+		(Some(0), _) | (_, Some(0)) => Rc::new(Expr::Const(0)),
+		(Some(1), _) => b,
+		(_, Some(1)) => a,
+		_ => Rc::new(Expr::Mul(a, b)),
+	}
+}
+
+fn less(a: Sym, b: Sym) -> Sym {
+	match (a.constant(), b.constant()) {
+		(Some(x), Some(y)) => Rc::new(Expr::Const((x < y) as isize)),
+		_ => Rc::new(Expr::Less(a, b)),
+	}
+}
+
+fn equal(a: Sym, b: Sym) -> Sym {
+	match (a.constant(), b.constant()) {
+		(Some(x), Some(y)) => Rc::new(Expr::Const((x == y) as isize)),
+		_ if a == b => Rc::new(Expr::Const(1)),
+		_ => Rc::new(Expr::Equal(a, b)),
+	}
+}
+
+fn constant(x: isize) -> Sym {
+	Rc::new(Expr::Const(x))
+}
+
+// A path condition: `expr` is nonzero exactly when `holds`.
+#[derive(Clone, Debug)]
+struct Cond {
+	expr: Sym,
+	holds: bool,
+}
+
+impl Cond {
+	fn check(&self, model: &[isize]) -> bool {
+		self.expr.eval(model).map(|x| (x != 0) == self.holds).unwrap_or(false)
+	}
+
+	// The linear form of the condition, when there is one. The rest are
+	// only checked once every input has a value.
+	fn atom(&self) -> Option<Atom> {
+		let (expr, holds) = (&self.expr, self.holds);
+		match &**expr {
+			Expr::Equal(a, b) if a.is_boolean() && b.constant() == Some(0) => {
+				Cond { expr: a.clone(), holds: !holds }.atom()
+			},
+			Expr::Equal(a, b) if a.is_boolean() && b.constant() == Some(1) => {
+				Cond { expr: a.clone(), holds }.atom()
+			},
+			Expr::Equal(a, b) => {
+				let rel = if holds { Rel::Zero } else { Rel::NonZero };
+				Some(Atom { lhs: a.linear()?.add(&b.linear()?, -1), rel })
+			},
+			Expr::Less(a, b) => {
+				let rel = if holds { Rel::Negative } else { Rel::NonNegative };
+				Some(Atom { lhs: a.linear()?.add(&b.linear()?, -1), rel })
+			},
+			_ => {
+				let rel = if holds { Rel::NonZero } else { Rel::Zero };
+				Some(Atom { lhs: expr.linear()?, rel })
+			},
+		}
+	}
+}
+
+#[derive(PartialEq, Eq, Clone, Default, Debug)]
+struct Linear {
+	terms: BTreeMap<usize, i128>,
+	constant: i128,
+}
+
+impl Linear {
+	fn add(&self, other: &Linear, factor: i128) -> Linear {
+		let mut sum = self.clone();
+		for (&k, &c) in other.terms.iter() {
+			let x = sum.terms.entry(k).or_insert(0);
+			*x += c * factor;
+			if *x == 0 {
+				sum.terms.remove(&k);
+			}
+		}
+		sum.constant += other.constant * factor;
+		sum
+	}
+
+	fn scale(&self, factor: i128) -> Linear {
+		Linear::default().add(self, factor)
+	}
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum Rel {
+	Zero,
+	NonZero,
+	Negative,
+	NonNegative,
+}
+
+// `lhs` compared against zero.
+#[derive(Clone, Debug)]
+struct Atom {
+	lhs: Linear,
+	rel: Rel,
+}
+
+impl Atom {
+	// Narrows the input intervals to what the atom allows, false when
+	// nothing is left.
+	fn narrow(&self, bounds: &mut [(i128, i128)]) -> bool {
+		let range = |skip: usize, bounds: &[(i128, i128)]| {
+			self.lhs.terms.iter().filter(|&(&k, _)| k != skip).fold((self.lhs.constant, self.lhs.constant), |(lo, hi), (&k, &c)| {
+				let (a, b) = (c * bounds[k].0, c * bounds[k].1);
+				(lo + a.min(b), hi + a.max(b))
+			})
+		};
+
+		for (&k, &c) in self.lhs.terms.iter() {
+			// c * x must land in [want.0, want.1] given the rest.
+			let (lo, hi) = range(k, bounds);
+			let want = match self.rel {
+				Rel::Zero => (-hi, -lo),
+				Rel::Negative => (i128::MIN / 4, -1 - lo),
+				Rel::NonNegative => (-hi, i128::MAX / 4),
+				Rel::NonZero => {
+					// Only an endpoint can be cut off.
+					if lo == hi && (-lo) % c == 0 {
+						let x = -lo / c;
+						let b = &mut bounds[k];
+						if b.0 == x {
+							b.0 += 1;
+						}
+						if b.1 == x {
+							b.1 -= 1;
+						}
+					}
+					continue;
+				},
+			};
+			let (x, y) = if c > 0 {
+				(div_ceil(want.0, c), div_floor(want.1, c))
+			} else {
+				(div_ceil(want.1, c), div_floor(want.0, c))
+			};
+			let b = &mut bounds[k];
+			b.0 = b.0.max(x);
+			b.1 = b.1.min(y);
+		}
+
+		let (lo, hi) = range(usize::MAX, bounds);
+		bounds.iter().all(|b| b.0 <= b.1) && match self.rel {
+			Rel::Zero => lo <= 0 && 0 <= hi,
+			Rel::NonZero => lo != 0 || hi != 0,
+			Rel::Negative => lo < 0,
+			Rel::NonNegative => hi >= 0,
+		}
+	}
+}
+
+fn div_floor(a: i128, b: i128) -> i128 {
+	let q = a / b;
+	if a % b != 0 && (a < 0) != (b < 0) { q - 1 } else { q }
+}
+
+fn div_ceil(a: i128, b: i128) -> i128 {
+	-div_floor(-a, b)
+}
+
+// Inputs, all within `domain`, satisfying every condition. Interval
+// propagation over the linear conditions, then splitting the widest
+// interval, trying `hint` first. Gives up after `budget` nodes.
+fn solve(conds: &[Cond], inputs: usize, domain: &RangeInclusive<isize>, hint: &[isize], budget: usize) -> Option<Vec<isize>> {
+	let atoms: Vec<Atom> = conds.iter().filter_map(|c| c.atom()).collect();
+	let bounds = vec![(*domain.start() as i128, *domain.end() as i128); inputs];
+
+	let mut nodes = 0;
+	let mut stack = vec![bounds];
+	while let Some(mut bounds) = stack.pop() {
+		nodes += 1;
+		if nodes > budget {
+			return None;
+		}
+
+		// A few rounds get close enough to the fixpoint.
+		let mut feasible = true;
+		for _ in 0..8 {
+			let before = bounds.clone();
+			feasible = atoms.iter().all(|a| a.narrow(&mut bounds));
+			if !feasible || before == bounds {
+				break;
+			}
+		}
+		if !feasible {
+			continue;
+		}
+
+		let open = (0..inputs)
+			.filter(|&k| bounds[k].0 < bounds[k].1)
+			.max_by_key(|&k| bounds[k].1 - bounds[k].0);
+		match open {
+			None => {
+				let model: Vec<isize> = bounds.iter().map(|b| b.0 as isize).collect();
+				if conds.iter().all(|c| c.check(&model)) {
+					return Some(model);
+				}
+			},
+			Some(k) => {
+				let (lo, hi) = bounds[k];
+				let guess = hint.get(k).map(|&x| x as i128).filter(|x| lo <= *x && *x <= hi);
+				let mut parts = match guess {
+					Some(x) => vec![(lo, x - 1), (x + 1, hi), (x, x)],
+					None => {
+						let mid = div_floor(lo + hi, 2);
+						vec![(mid + 1, hi), (lo, mid)]
+					},
+				};
+				parts.retain(|p| p.0 <= p.1);
+				for p in parts {
+					let mut next = bounds.clone();
+					next[k] = p;
+					stack.push(next);
+				}
+			},
+		}
+	}
+
+	None
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum Goal {
+	Reach(usize),
+	Output(isize),
+}
+
+#[derive(Clone, Debug)]
+struct Limits {
+	// Where inputs are looked for.
+	domain: RangeInclusive<isize>,
+	inputs: usize,
+	// Cells addresses, jump targets and the relative base may use.
+	memory: usize,
+	steps: usize,
+	paths: usize,
+	solver: usize,
+}
+
+impl Default for Limits {
+	fn default() -> Limits {
+		Limits {
+			domain: -1_000_000_000..=1_000_000_000,
+			inputs: 16,
+			memory: 1 << 20,
+			steps: 1_000_000,
+			paths: 1000,
+			solver: 10_000,
+		}
+	}
+}
+
+// One way through the program. Along with the symbolic state it keeps
+// `model`, inputs which take exactly this way: addresses, jump targets
+// and opcodes depending on inputs get pinned to their value under it.
+// A path going outside `memory` ends there.
+#[derive(Clone)]
+struct Path {
+	ram: Vec<Sym>,
+	pc: usize,
+	rb: usize,
+	memory: usize,
+	inputs: usize,
+	conds: Vec<Cond>,
+	model: Vec<isize>,
+	steps: usize,
+}
+
+impl Path {
+	fn load(&mut self, i: usize) -> Sym {
+		if i >= self.ram.len() {
+			self.ram.resize(i + 1, constant(0));
+		}
+		self.ram[i].clone()
+	}
+
+	// `i` as an address, when it is one.
+	fn index(&self, i: isize) -> Option<usize> {
+		if 0 <= i && (i as usize) < self.memory {
+			Some(i as usize)
+		} else {
+			None
+		}
+	}
+
+	fn address(&self, p: Param) -> Option<usize> {
+		match p {
+			Param::Pos(i) => self.index(i as isize),
+			Param::Rel(i) => self.index(self.rb as isize + i),
+			Param::Imm(_) => panic!("incorrect destination"),
+		}
+	}
+
+	// Immediate operands keep their symbolic value.
+	fn load_value(&mut self, p: Param, cell: usize) -> Option<Sym> {
+		match p {
+			Param::Imm(_) => Some(self.load(self.pc + cell)),
+			_ => {
+				let i = self.address(p)?;
+				Some(self.load(i))
+			},
+		}
+	}
+
+	fn write_value(&mut self, p: Param, x: Sym) -> Option<()> {
+		let i = self.address(p)?;
+		self.load(i);
+		self.ram[i] = x;
+		Some(())
+	}
+
+	// The value under the model, held to it from now on.
+	fn pin(&mut self, x: &Sym) -> Option<isize> {
+		let v = x.eval(&self.model)?;
+		if x.constant().is_none() {
+			self.conds.push(Cond { expr: equal(x.clone(), constant(v)), holds: true });
+		}
+		Some(v)
+	}
+
+	fn decode(&mut self) -> Option<(Op, usize)> {
+		let opcode = self.load(self.pc);
+		let opcode = self.pin(&opcode)?;
+		let len = match opcode % 100 {
+			_ if opcode < 0 => return None,
+			1 | 2 | 7 | 8 => 4,
+			5 | 6 => 3,
+			3 | 4 | 9 => 2,
+			99 => 1,
+			_ => return None,
+		};
+
+		let mut cells = vec![opcode];
+		let mut modes = opcode / 100;
+		for i in 1..len {
+			// Immediates stay symbolic; only addresses get pinned.
+			let cell = self.load(self.pc + i);
+			let value = match modes % 10 {
+				0 => {
+					let value = self.pin(&cell)?;
+					self.index(value)?;
+					value
+				},
+				2 => self.pin(&cell)?,
+				1 => cell.eval(&self.model)?,
+				_ => return None,
+			};
+			cells.push(value);
+			modes /= 10;
+		}
+		cells.resize(4, 0);
+		Some(decode(&cells))
+	}
+
+	fn jump(&mut self, taken: bool, target: &Sym, len: usize) -> Option<()> {
+		if taken {
+			let target = self.pin(target)?;
+			self.pc = self.index(target)?;
+		} else {
+			self.pc += len;
+		}
+		Some(())
+	}
+
+	// Follows the model until the goal is met, giving the inputs, or the
+	// path ends. Feasible ways the model does not take go to `forks`.
+	fn run(&mut self, goal: Goal, limits: &Limits, forks: &mut Vec<Path>) -> Option<Vec<isize>> {
+		loop {
+			if self.steps >= limits.steps {
+				return None;
+			}
+			self.steps += 1;
+
+			if goal == Goal::Reach(self.pc) {
+				return Some(self.model.clone());
+			}
+
+			let (op, len) = self.decode()?;
+			match op {
+				Op::Add(a, b, c) | Op::Mul(a, b, c) | Op::CmpLess(a, b, c) | Op::CmpEq(a, b, c) => {
+					let a = self.load_value(a, 1)?;
+					let b = self.load_value(b, 2)?;
+					let x = match op {
+						Op::Add(..) => add(a, b),
+						Op::Mul(..) => mul(a, b),
+						Op::CmpLess(..) => less(a, b),
+						_ => equal(a, b),
+					};
+					self.write_value(c, x)?;
+				},
+				Op::In(a) => {
+					if self.inputs >= limits.inputs {
+						return None;
+					}
+					let x = Rc::new(Expr::Input(self.inputs));
+					self.inputs += 1;
+					let guess = 0.max(*limits.domain.start()).min(*limits.domain.end());
+					self.model.push(guess);
+					self.write_value(a, x)?;
+				},
+				Op::Out(a) => {
+					let x = self.load_value(a, 1)?;
+					if let Goal::Output(target) = goal {
+						if x.eval(&self.model) == Some(target) {
+							return Some(self.model.clone());
+						}
+						if x.constant().is_none() {
+							let mut conds = self.conds.clone();
+							conds.push(Cond { expr: equal(x, constant(target)), holds: true });
+							let found = solve(&conds, self.inputs, &limits.domain, &self.model, limits.solver);
+							if found.is_some() {
+								return found;
+							}
+						}
+					}
+				},
+				Op::JmpTrue(a, b) | Op::JmpFalse(a, b) => {
+					let on = matches!(op, Op::JmpTrue(..));
+					let cond = self.load_value(a, 1)?;
+					let target = self.load_value(b, 2)?;
+
+					let nonzero = cond.eval(&self.model)? != 0;
+					if cond.constant().is_none() {
+						let mut conds = self.conds.clone();
+						conds.push(Cond { expr: cond.clone(), holds: !nonzero });
+						if let Some(model) = solve(&conds, self.inputs, &limits.domain, &self.model, limits.solver) {
+							let mut fork = Path { conds, model, ..self.clone() };
+							if fork.jump(nonzero != on, &target, len).is_some() {
+								forks.push(fork);
+							}
+						}
+						self.conds.push(Cond { expr: cond, holds: nonzero });
+					}
+					self.jump(nonzero == on, &target, len)?;
+					continue;
+				},
+				Op::AdjustBase(a) => {
+					let x = self.load_value(a, 1)?;
+					let offset = self.pin(&x)?;
+					self.rb = self.index(self.rb as isize + offset)?;
+				},
+				Op::Halt => return None,
+			}
+
+			self.pc += len;
+		}
+	}
+}
+
+// Inputs driving `rom` to `goal`, found by running it on symbolic inputs
+// and forking wherever a jump depends on them, shortest paths first.
+fn explore(rom: &[isize], goal: Goal, limits: &Limits) -> Option<Vec<isize>> {
+	let start = Path {
+		ram: rom.iter().map(|&x| constant(x)).collect(),
+		pc: 0,
+		rb: 0,
+		memory: limits.memory,
+		inputs: 0,
+		conds: Vec::new(),
+		model: Vec::new(),
+		steps: 0,
+	};
+
+	let mut queue = VecDeque::new();
+	queue.push_back(start);
+	let mut paths = 1;
+
+	let mut forks = Vec::new();
+	while let Some(mut path) = queue.pop_front() {
+		if let Some(inputs) = path.run(goal, limits, &mut forks) {
+			return Some(inputs);
+		}
+		for fork in forks.drain(..) {
+			if paths < limits.paths {
+				paths += 1;
+				queue.push_back(fork);
+			}
+		}
+	}
+
+	None
+}
+
 #[derive(PartialOrd, Ord, PartialEq, Eq, Clone, Copy, Debug)]
 enum State {
 	Ready = 0,
@@ -331,6 +927,75 @@ mod tests {
 		assert_eq!(output[0], 1125899906842624);
 	}
 
+	#[test]
+	fn exploring() {
+		let limits = Limits::default();
+
+		// Prints 1 when the input is 1234, 0 otherwise.
+		let rom = parse("3,20,1008,20,1234,21,1005,21,12,104,0,99,104,1,99,0,0,0,0,0,0,0").unwrap();
+		assert_eq!(explore(&rom, Goal::Reach(12), &limits), Some(vec![1234]));
+		assert_eq!(explore(&rom, Goal::Output(1), &limits), Some(vec![1234]));
+		assert_eq!(explore(&rom, Goal::Output(0), &limits), Some(vec![0]));
+		assert_eq!(explore(&rom, Goal::Output(7), &limits), None);
+
+		// Prints 1 when 3 * a + b == 50 and a < b.
+		let mut code = "3,100,3,101,1002,100,3,102,1,102,101,102,1008,102,50,103,1006,103,29,7,100,101,104,1006,104,29,104,1,99,104,0,99".to_string();
+		code += &",0".repeat(80);
+		let rom = parse(&code).unwrap();
+		let inputs = explore(&rom, Goal::Output(1), &limits).unwrap();
+		assert_eq!(3 * inputs[0] + inputs[1], 50);
+		assert!(inputs[0] < inputs[1]);
+		assert_eq!(feed(&rom, &inputs), [1]);
+
+		// Never both above and below.
+		let mut code = "3,100,1007,100,10,101,1006,101,18,1007,100,20,101,1005,101,18,104,1,99".to_string();
+		code += &",0".repeat(90);
+		let rom = parse(&code).unwrap();
+		assert_eq!(explore(&rom, Goal::Output(1), &limits), None);
+
+		// The input lands in the operand of the output instruction.
+		let rom = parse("3,3,104,0,99").unwrap();
+		assert_eq!(explore(&rom, Goal::Output(42), &limits), Some(vec![42]));
+
+		// Only a negative input gets to 10, which outputs from it as an
+		// address; the path ends there instead of wrapping around.
+		let mut code = "3,11,1007,11,0,20,1005,20,10,99,4,0,99,0".to_string();
+		code += &",0".repeat(7);
+		let rom = parse(&code).unwrap();
+		assert_eq!(explore(&rom, Goal::Reach(10), &limits).map(|i| i[0] < 0), Some(true));
+		assert_eq!(explore(&rom, Goal::Reach(12), &limits), None);
+	}
+
+	#[test]
+	fn solving() {
+		let a = Rc::new(Expr::Input(0));
+		let b = Rc::new(Expr::Input(1));
+		let conds = [
+			Cond { expr: equal(add(mul(a.clone(), constant(2)), b.clone()), constant(7)), holds: true },
+			Cond { expr: less(b.clone(), a.clone()), holds: true },
+			Cond { expr: b.clone(), holds: true },
+		];
+		let model = solve(&conds, 2, &(0..=10), &[], 1000).unwrap();
+		assert_eq!(model, [3, 1]);
+		assert!(conds.iter().all(|c| c.check(&model)));
+
+		// a * a == 49 is only checked once a has a value.
+		let square = [Cond { expr: equal(mul(a.clone(), a.clone()), constant(49)), holds: true }];
+		assert_eq!(solve(&square, 1, &(0..=10), &[], 1000), Some(vec![7]));
+		assert_eq!(solve(&square, 1, &(-10..=-1), &[], 1000), Some(vec![-7]));
+		assert_eq!(solve(&square, 1, &(0..=5), &[], 1000), None);
+	}
+
+	fn feed(rom: &[isize], inputs: &[isize]) -> Vec<isize> {
+		let mut m = Machine::from(rom);
+		for &x in inputs {
+			m.feed(x);
+		}
+		let mut output = Vec::new();
+		assert_eq!(m.run(&mut output), State::Halted);
+		output
+	}
+
 	fn run(code: &str) -> Vec<isize> {
 		let rom = parse(code).unwrap();
 		let mut m = Machine::from(&rom);
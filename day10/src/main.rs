@@ -1,5 +1,6 @@
 use std::io::{self, Read};
-use std::collections::HashSet;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Result<T, E = Error> = std::result::Result<T, E>;
@@ -11,7 +12,7 @@ fn main() -> Result<()> {
 	let asteroids = parse(&input);
 
 	println!("p1: {}", solve_part1(&asteroids));
-	let (x, y) = vaporize(&asteroids, 200);
+	let (x, y) = vaporize(&asteroids, 200).ok_or("fewer than 200 asteroids to vaporize")?;
 	println!("p2: {:?}", x * 100 + y);
 
 	Ok(())
@@ -22,25 +23,56 @@ fn solve_part1(asteroids: &[(i64, i64)]) -> usize {
 	los.len()
 }
 
-fn vaporize(asteroids: &[(i64, i64)], i: usize) -> (i64, i64) {
-	let (s, los) = place_station(asteroids);
-	let mut rays = los
-		.iter()
-		.map(|&(dx, dy)| ((dx, dy), (dx as f32).atan2(dy as f32)))
-		.collect::<Vec<_>>();
+fn vaporize(asteroids: &[(i64, i64)], i: usize) -> Option<(i64, i64)> {
+	let (s, _) = place_station(asteroids);
+	destruction_order(s, asteroids).get(i.checked_sub(1)?).copied()
+}
 
-	rays.sort_by(|(_, a0), (_, a1)| a1.partial_cmp(a0).unwrap());
+// Every asteroid but the station, in the order the laser hits them. It
+// starts pointing up and turns clockwise, taking the nearest asteroid on
+// each ray per rotation.
+fn destruction_order(s: (i64, i64), asteroids: &[(i64, i64)]) -> Vec<(i64, i64)> {
+	let mut rays: HashMap<(i64, i64), Vec<i64>> = HashMap::new();
+	for a in asteroids {
+		if *a != s {
+			let (ray, steps) = direction(&s, a);
+			rays.entry(ray).or_default().push(steps);
+		}
+	}
 
-	let asteroids = asteroids.into_iter().collect::<HashSet<_>>();
+	let mut order = rays.keys().copied().collect::<Vec<_>>();
+	order.sort_by(|&a, &b| clockwise(a, b));
+	for ray in rays.values_mut() {
+		// Nearest last, to pop.
+		ray.sort_by_key(|&steps| -steps);
+	}
 
-	let (dx, dy) = rays[i - 1].0;
-	let (mut x, mut y) = (s.0 + dx, s.1 + dy);
-	while !asteroids.contains(&(x, y)) {
-		x += dx;
-		y += dy;
+	let mut hit = Vec::new();
+	while hit.len() < asteroids.len() - 1 {
+		for ray in &order {
+			if let Some(steps) = rays.get_mut(ray).unwrap().pop() {
+				hit.push((s.0 + ray.0 * steps, s.1 + ray.1 * steps));
+			}
+		}
 	}
-	
-	(x, y)
+	hit
+}
+
+// Orders directions by the angle clockwise from straight up, with y
+// pointing down. Each quarter turn starts on an axis, and within one the
+// cross product says which comes first, so no precision is lost.
+fn clockwise(a: (i64, i64), b: (i64, i64)) -> Ordering {
+	fn quadrant((dx, dy): (i64, i64)) -> u8 {
+		match (dx.signum(), dy.signum()) {
+			(0, -1) | (1, -1) => 0,
+			(1, 0) | (1, 1) => 1,
+			(0, 1) | (-1, 1) => 2,
+			_ => 3,
+		}
+	}
+
+	let cross = a.0 as i128 * b.1 as i128 - a.1 as i128 * b.0 as i128;
+	quadrant(a).cmp(&quadrant(b)).then(0.cmp(&cross))
 }
 
 fn place_station(asteroids: &[(i64, i64)]) -> ((i64, i64), HashSet<(i64, i64)>) {
@@ -65,14 +97,19 @@ fn find_visible(a: &(i64, i64), asteroids: &[(i64, i64)]) -> HashSet<(i64, i64)>
 			continue;
 		}
 
-		let dx = other.0 - a.0;
-		let dy = other.1 - a.1;
-		let d = gcd(dx, dy).abs();
-		los.insert((dx / d, dy / d));
+		los.insert(direction(a, other).0);
 	}
 	los
 }
 
+// The reduced step from a towards b, and how many of them it takes.
+fn direction(a: &(i64, i64), b: &(i64, i64)) -> ((i64, i64), i64) {
+	let dx = b.0 - a.0;
+	let dy = b.1 - a.1;
+	let d = gcd(dx, dy).abs();
+	((dx / d, dy / d), d)
+}
+
 fn gcd(mut x: i64, mut y: i64) -> i64 {
 	while y != 0 {
 		let t = y;
@@ -240,8 +277,53 @@ mod tests {
 			###.##.####.##.#..##
 		";
 		let asteroids = parse(&input);
-		assert_eq!(vaporize(&asteroids, 1),   (11, 12));
-		assert_eq!(vaporize(&asteroids, 2),   (12, 1));
-		assert_eq!(vaporize(&asteroids, 200), (8, 2));
+		assert_eq!(vaporize(&asteroids, 1),   Some((11, 12)));
+		assert_eq!(vaporize(&asteroids, 2),   Some((12, 1)));
+		assert_eq!(vaporize(&asteroids, 200), Some((8, 2)));
+		assert_eq!(vaporize(&asteroids, 201), Some((10, 9)));
+		assert_eq!(vaporize(&asteroids, 299), Some((11, 1)));
+		assert_eq!(vaporize(&asteroids, 300), None);
+		assert_eq!(vaporize(&asteroids, 0), None);
+	}
+
+	#[test]
+	fn rotations() {
+		let input = r"
+			.#....#####...#..
+			##...##.#####..##
+			##...#...#.#####.
+			..#.....#...###..
+			..#.#.....#....##
+		";
+		let asteroids = parse(&input);
+		let order = destruction_order((8, 3), &asteroids);
+		assert_eq!(order.len(), asteroids.len() - 1);
+		assert_eq!(&order[..], &[
+			(8,1), (9,0), (9,1), (10,0), (9,2), (11,1), (12,1), (11,2), (15,1),
+			(12,2), (13,2), (14,2), (15,2), (12,3), (16,4), (15,4), (10,4), (4,4),
+			(2,4), (2,3), (0,2), (1,2), (0,1), (1,1), (5,2), (1,0), (5,1),
+			(6,1), (6,0), (7,0), (8,0), (10,1), (14,0), (16,1), (13,3), (14,3),
+		]);
+
+		// A column straight up takes one rotation per asteroid.
+		let column = [(0, 0), (0, 1), (0, 2), (1, 3), (0, 3)];
+		assert_eq!(destruction_order((0, 3), &column), [(0, 2), (1, 3), (0, 1), (0, 0)]);
+	}
+
+	#[test]
+	fn angles() {
+		let compass = [(0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1)];
+		let mut sorted = compass;
+		sorted.reverse();
+		sorted.sort_by(|&a, &b| clockwise(a, b));
+		assert_eq!(sorted, compass);
+
+		// Too close together for f32 to tell apart.
+		let a = (1_000_000, -999_999);
+		let b = (999_999, -999_998);
+		assert_eq!((a.0 as f32).atan2(a.1 as f32), (b.0 as f32).atan2(b.1 as f32));
+		assert_eq!(clockwise(a, b), Ordering::Less);
+		assert_eq!(clockwise(b, a), Ordering::Greater);
+		assert_eq!(clockwise(a, a), Ordering::Equal);
 	}
 }
@@ -1,5 +1,11 @@
-use std::io::{self, Read};
+use std::io::{self, Read, Write};
 use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+use bitmap::{ocr, Bitmap};
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Result<T, E = Error> = std::result::Result<T, E>;
@@ -15,54 +21,314 @@ macro_rules! trace {
 }
 
 fn main() -> Result<()> {
-	let mut input = String::new();
-	io::stdin().read_to_string(&mut input)?;
+	let mut args = env::args().skip(1).peekable();
+
+	// `--script FILE` paints from the paint and turn pairs in the file,
+	// as the program would output them, instead of running it.
+	let robot = if args.peek().map(String::as_str) == Some("--script") {
+		args.next();
+		let path = args.next().ok_or("--script needs a file")?;
+		let mut brain = Script::parse(&fs::read_to_string(path)?)?;
+		let mut robot = Robot::new();
+		robot.history = Some(Vec::new());
+		robot.run(&mut brain, 0);
+		dump(&robot.map);
+		robot
+	} else {
+		let mut input = String::new();
+		io::stdin().read_to_string(&mut input)?;
+
+		let rom = parse(&input)?;
+
+		let p1 = run(&rom, 0, false).painted;
+		println!("p1: {}", p1);
+
+		let robot = run(&rom, 1, true);
+		dump(&robot.map);
+		println!("p2: {}", ocr(&to_bitmap(&robot.map))?);
+		robot
+	};
+	let hull = to_bitmap(&robot.map);
+
+	// `--animate` replays painting in the terminal, `--frames DIR`
+	// writes every step as a PBM, any other argument is a file to
+	// export the hull to, by extension.
+	let history = robot.history.as_ref().ok_or("painting was not recorded")?;
+	while let Some(arg) = args.next() {
+		if arg == "--animate" {
+			animate(history, Duration::from_millis(20));
+		} else if arg == "--frames" {
+			let dir = args.next().ok_or("--frames needs a directory")?;
+			fs::create_dir_all(&dir)?;
+			// Each frame is written as it comes, the first failure stops
+			// the writing.
+			let area = bounds(history);
+			let mut i = 0;
+			let mut written = Ok(());
+			replay(history, |map, pos, _| {
+				if written.is_ok() {
+					let frame = frame_bitmap(map, area, pos);
+					written = fs::write(format!("{}/frame{:05}.pbm", dir, i), frame.scaled(8).to_pbm());
+				}
+				i += 1;
+			});
+			written?;
+		} else if arg.ends_with(".png") {
+			fs::write(&arg, hull.scaled(8).to_png())?;
+		} else if arg.ends_with(".pbm") {
+			fs::write(&arg, hull.to_pbm())?;
+		} else {
+			return Err(format!("unsupported image format: {}", arg).into());
+		}
+	}
+	
+	Ok(())
+}
 
-	let rom = parse(&input)?;
+fn run(rom: &[isize], start: isize, record: bool) -> Robot {
+	let mut robot = Robot::new();
+	if record {
+		robot.history = Some(Vec::new());
+	}
+	robot.run(&mut Rom::from(rom), start as u8);
+	robot
+}
 
-	let (_, p1) = run(&rom, 0);
-	println!("p1: {}", p1);
+// Decides what to paint and where to turn given the color under the
+// robot, `None` stops the robot.
+trait Brain {
+	fn think(&mut self, cam: u8) -> Option<(u8, Turn)>;
+}
 
-	let (map, _) = run(&rom, 1);
-	println!("p1:");
-	dump(&map);
-	
-	Ok(())
+impl<F: FnMut(u8) -> Option<(u8, Turn)>> Brain for F {
+	fn think(&mut self, cam: u8) -> Option<(u8, Turn)> {
+		self(cam)
+	}
 }
 
-fn run(rom: &[isize], start: isize) -> (HashMap::<(i64, i64), u8>, i64) {
-	let mut painted = 0;
-	let mut map = HashMap::<(i64, i64), u8>::new();
-	let mut pos = (0, 0);
-	let mut dir = Dir::N;
-	
-	let mut scratch = Vec::new();
-	let mut m = Machine::from(&rom);
-	m.feed(start);
-	loop {
-		if m.run(&mut scratch) == State::Halted {
-			break;
+struct Rom {
+	machine: Machine,
+	output: Vec<isize>,
+}
+
+impl Rom {
+	fn from(rom: &[isize]) -> Rom {
+		Rom {
+			machine: Machine::from(rom),
+			output: Vec::new(),
 		}
-		let color = scratch[0];
-		let rotation = scratch[1];
-		scratch.clear();
+	}
+}
 
-		*map.entry(pos).or_insert_with(|| {
-			painted += 1;
-			color as u8
-		}) = color as u8;
-		
-		dir = rotate_robot(dir, rotation);
-		pos = move_robot(pos, dir);
+impl Brain for Rom {
+	fn think(&mut self, cam: u8) -> Option<(u8, Turn)> {
+		self.machine.feed(cam as isize);
+		self.output.clear();
+		if self.machine.run(&mut self.output) == State::Halted {
+			return None;
+		}
+		// Stopping short of a whole command ends the run too.
+		let turn = match *self.output.get(1)? {
+			0 => Turn::Left,
+			1 => Turn::Right,
+			_ => panic!("unsupported rotation command"),
+		};
+		Some((self.output[0] as u8, turn))
+	}
+}
+
+// Replays a fixed list of paint and turn commands.
+struct Script {
+	cmds: Vec<(u8, Turn)>,
+	next: usize,
+}
+
+impl Script {
+	fn from(cmds: &[(u8, Turn)]) -> Script {
+		Script {
+			cmds: cmds.to_vec(),
+			next: 0,
+		}
+	}
+
+	// Pairs of color and turn, 0 for left and 1 for right, in any mix
+	// of commas and whitespace.
+	fn parse(s: &str) -> Result<Script> {
+		let values = s.split(|c: char| c == ',' || c.is_whitespace())
+			.filter(|v| !v.is_empty())
+			.map(|v| v.parse::<u8>())
+			.collect::<Result<Vec<_>, _>>()?;
+		if values.is_empty() || values.len() % 2 != 0 {
+			return Err("a script is pairs of color and turn".into());
+		}
+
+		let cmds = values.chunks(2).map(|pair| match *pair {
+			[color @ 0..=1, 0] => Ok((color, Turn::Left)),
+			[color @ 0..=1, 1] => Ok((color, Turn::Right)),
+			_ => Err(format!("bad command {:?}", pair)),
+		}).collect::<Result<Vec<_>, _>>()?;
+		Ok(Script::from(&cmds))
+	}
+}
+
+impl Brain for Script {
+	fn think(&mut self, _: u8) -> Option<(u8, Turn)> {
+		let cmd = self.cmds.get(self.next).cloned();
+		self.next += 1;
+		cmd
+	}
+}
+
+// What the robot did at a single step, `fresh` tells if the panel
+// counted as newly painted.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+struct Step {
+	pos: (i64, i64),
+	dir: Dir,
+	color: u8,
+	turn: Turn,
+	fresh: bool,
+}
+
+struct Robot {
+	map: HashMap<(i64, i64), u8>,
+	painted: i64,
+	pos: (i64, i64),
+	dir: Dir,
+	history: Option<Vec<Step>>,
+}
+
+impl Robot {
+	fn new() -> Robot {
+		Robot {
+			map: HashMap::new(),
+			painted: 0,
+			pos: (0, 0),
+			dir: Dir::N,
+			history: None,
+		}
+	}
+
+	// `start` is what the camera sees first, it is not painted.
+	fn run(&mut self, brain: &mut impl Brain, start: u8) {
+		let mut cam = start;
+		while let Some((color, turn)) = brain.think(cam) {
+			self.step(color, turn);
+			cam = self.camera();
+		}
+	}
+
+	fn step(&mut self, color: u8, turn: Turn) {
+		let before = self.painted;
+		let painted = &mut self.painted;
+		*self.map.entry(self.pos).or_insert_with(|| {
+			*painted += 1;
+			color
+		}) = color;
+
+		if let Some(history) = self.history.as_mut() {
+			history.push(Step {
+				pos: self.pos,
+				dir: self.dir,
+				color,
+				turn,
+				fresh: self.painted != before,
+			});
+		}
 
-		let cam = *map.get(&pos).unwrap_or(&0);
-		m.feed(cam as isize);
+		self.dir = rotate_robot(self.dir, turn);
+		self.pos = move_robot(self.pos, self.dir);
 	}
 
-	(map, painted)
+	fn camera(&self) -> u8 {
+		*self.map.get(&self.pos).unwrap_or(&0)
+	}
 }
 
 fn dump(map: &HashMap<(i64, i64), u8>) {
+	to_bitmap(map).dump();
+}
+
+// Paints the hull again from a recording, calling `frame` with the
+// hull and the robot pose before the first and after every step.
+fn replay(history: &[Step], mut frame: impl FnMut(&HashMap<(i64, i64), u8>, (i64, i64), Dir)) {
+	let mut robot = Robot::new();
+	frame(&robot.map, robot.pos, robot.dir);
+	for s in history {
+		assert_eq!((robot.pos, robot.dir), (s.pos, s.dir), "recording went off track");
+		let before = robot.painted;
+		robot.step(s.color, s.turn);
+		assert_eq!(robot.painted != before, s.fresh, "painted count diverged");
+		frame(&robot.map, robot.pos, robot.dir);
+	}
+}
+
+// Everywhere the robot has been, as `(xmin, ymin, w, h)`.
+fn bounds(history: &[Step]) -> (i64, i64, usize, usize) {
+	let mut xmin = 0;
+	let mut xmax = 0;
+	let mut ymin = 0;
+	let mut ymax = 0;
+	for s in history {
+		for &(x, y) in [s.pos, move_robot(s.pos, rotate_robot(s.dir, s.turn))].iter() {
+			xmin = xmin.min(x);
+			xmax = xmax.max(x);
+			ymin = ymin.min(y);
+			ymax = ymax.max(y);
+		}
+	}
+	(xmin, ymin, (xmax - xmin + 1) as usize, (ymax - ymin + 1) as usize)
+}
+
+// White panels are lit, the robot shows up inverted.
+fn frame_bitmap(map: &HashMap<(i64, i64), u8>, area: (i64, i64, usize, usize), pos: (i64, i64)) -> Bitmap {
+	let (xmin, ymin, w, h) = area;
+	let mut pixels = vec![false; w * h];
+	for (&(x, y), &c) in map.iter() {
+		pixels[(y - ymin) as usize * w + (x - xmin) as usize] = c == 1;
+	}
+	let i = (pos.1 - ymin) as usize * w + (pos.0 - xmin) as usize;
+	pixels[i] = !pixels[i];
+	Bitmap { w, h, pixels }
+}
+
+fn animate(history: &[Step], delay: Duration) {
+	let (xmin, ymin, w, h) = bounds(history);
+	let mut i = 0;
+	let mut painted = 0;
+	replay(history, |map, pos, dir| {
+		if i > 0 && history[i - 1].fresh {
+			painted += 1;
+		}
+		let mut out = String::from("\x1b[H\x1b[2J");
+		for y in ymin..ymin + h as i64 {
+			for x in xmin..xmin + w as i64 {
+				out.push(if (x, y) == pos {
+					match dir {
+						Dir::N => '^',
+						Dir::S => 'v',
+						Dir::W => '<',
+						Dir::E => '>',
+					}
+				} else if map.get(&(x, y)) == Some(&1) {
+					'█'
+				} else if map.contains_key(&(x, y)) {
+					'·'
+				} else {
+					' '
+				});
+			}
+			out.push('\n');
+		}
+		out.push_str(&format!("step {}/{}, painted {}\n", i, history.len(), painted));
+		print!("{}", out);
+		let _ = io::stdout().flush();
+		thread::sleep(delay);
+		i += 1;
+	});
+}
+
+fn to_bitmap(map: &HashMap<(i64, i64), u8>) -> Bitmap {
 	let mut xmin = std::i64::MAX;
 	let mut xmax = std::i64::MIN;
 	let mut ymin = std::i64::MAX;
@@ -76,37 +342,26 @@ fn dump(map: &HashMap<(i64, i64), u8>) {
 	
 	let w = (xmax - xmin + 1) as usize;
 	let h = (ymax - ymin + 1) as usize;
-	let mut rows = Vec::new();
-	for _ in 0..h {
-		let mut row = Vec::<u8>::new();
-		row.resize(w, 0);
-		rows.push(row);
-	}
+	let mut pixels = vec![false; w * h];
 	for ((x, y), c) in map.iter() {
-		let x = x - xmin;
-		let y = y - ymin;
-		rows[y as usize][x as usize] = *c;
+		let x = (x - xmin) as usize;
+		let y = (y - ymin) as usize;
+		pixels[y * w + x] = *c == 1;
 	}
 
-	for y in 0..h {
-		for x in 0..w {
-			print!("{}", if rows[y][x] == 1 { '█' } else { ' ' });
-		}
-		println!();
-	}
+	Bitmap { w, h, pixels }
 }
 
-fn rotate_robot(dir: Dir, r: isize) -> Dir {
-	match (dir, r) {
-		(Dir::N, 0) => Dir::W,
-		(Dir::N, 1) => Dir::E,
-		(Dir::S, 0) => Dir::E,
-		(Dir::S, 1) => Dir::W,
-		(Dir::W, 0) => Dir::S,
-		(Dir::W, 1) => Dir::N,
-		(Dir::E, 0) => Dir::N,
-		(Dir::E, 1) => Dir::S,
-		_ => panic!("unsupported rotation command"),
+fn rotate_robot(dir: Dir, turn: Turn) -> Dir {
+	match (dir, turn) {
+		(Dir::N, Turn::Left) => Dir::W,
+		(Dir::N, Turn::Right) => Dir::E,
+		(Dir::S, Turn::Left) => Dir::E,
+		(Dir::S, Turn::Right) => Dir::W,
+		(Dir::W, Turn::Left) => Dir::S,
+		(Dir::W, Turn::Right) => Dir::N,
+		(Dir::E, Turn::Left) => Dir::N,
+		(Dir::E, Turn::Right) => Dir::S,
 	}
 }
 
@@ -121,7 +376,7 @@ fn move_robot(pos: (i64, i64), dir: Dir) -> (i64, i64) {
 	(pos.0 + dx, pos.1 + dy)
 }
 
-#[derive(PartialOrd, Ord, PartialEq, Eq, Clone, Copy)]
+#[derive(PartialOrd, Ord, PartialEq, Eq, Clone, Copy, Debug)]
 enum Dir {
 	N,
 	S,
@@ -129,6 +384,12 @@ enum Dir {
 	E,
 }
 
+#[derive(PartialOrd, Ord, PartialEq, Eq, Clone, Copy, Debug)]
+enum Turn {
+	Left,
+	Right,
+}
+
 fn parse(input: &str) -> Result<Box<[isize]>> {
 	let ram: Vec<isize> = input
 		.trim()
@@ -402,3 +663,141 @@ fn decode_param(mode: u8, value: isize) -> Param {
 		_ => panic!("unknown parameter mode"),
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn example() {
+		let mut brain = Script::from(&[
+			(1, Turn::Left),
+			(0, Turn::Left),
+			(1, Turn::Left),
+			(1, Turn::Left),
+			(0, Turn::Right),
+			(1, Turn::Left),
+			(1, Turn::Left),
+		]);
+		let mut robot = Robot::new();
+		robot.run(&mut brain, 0);
+		assert_eq!(robot.painted, 6);
+		assert_eq!((robot.pos, robot.dir), ((0, -1), Dir::W));
+		assert_eq!(robot.map.values().filter(|&&c| c == 1).count(), 4);
+	}
+
+	#[test]
+	fn closure() {
+		// Paints everything white and keeps turning right, walking a
+		// 2x2 square forever.
+		let mut seen = Vec::new();
+		let mut brain = |cam| {
+			seen.push(cam);
+			if seen.len() > 8 { None } else { Some((1, Turn::Right)) }
+		};
+		let mut robot = Robot::new();
+		robot.run(&mut brain, 0);
+		assert_eq!(robot.painted, 4);
+		assert_eq!(seen, [0, 0, 0, 0, 1, 1, 1, 1, 1]);
+		assert_eq!((robot.pos, robot.dir), ((0, 0), Dir::N));
+	}
+
+	#[test]
+	fn brains() {
+		// The example as the program would output it.
+		let mut brain = Script::parse("1,0, 0,0, 1,0, 1,0,\n0,1, 1,0, 1,0\n").unwrap();
+		let mut robot = Robot::new();
+		robot.run(&mut brain, 0);
+		assert_eq!(robot.painted, 6);
+		assert_eq!((robot.pos, robot.dir), ((0, -1), Dir::W));
+
+		assert!(Script::parse("").is_err());
+		assert!(Script::parse("1,0,1").is_err());
+		assert!(Script::parse("1,2").is_err());
+		assert!(Script::parse("2,0").is_err());
+
+		// Asks for the next color after painting, before saying where
+		// to turn.
+		let rom = parse("3,7,104,1,3,7,99,0").unwrap();
+		let mut robot = Robot::new();
+		robot.run(&mut Rom::from(&rom), 0);
+		assert_eq!(robot.painted, 0);
+	}
+
+	const TEXT: &str = "\
+.##..###...##..####.####..##..#..#...##.#..#.#....###..###..#..#..###.####
+#..#.#..#.#..#.#....#....#..#.#..#....#.#.#..#....#..#.#..#.#..#.#.......#
+#..#.###..#....###..###..#....####....#.##...#....#..#.#..#.#..#.#......#.
+####.#..#.#....#....#....#.##.#..#....#.#.#..#....###..###..#..#..##...#..
+#..#.#..#.#..#.#....#....#..#.#..#.#..#.#.#..#....#....#.#..#..#....#.#...
+#..#.###...##..####.#.....###.#..#..##..#..#.####.#....#..#..##..###..####";
+
+	fn bitmap(s: &str) -> Bitmap {
+		let rows: Vec<&str> = s.lines().collect();
+		let pixels = rows.iter().flat_map(|r| r.bytes().map(|b| b == b'#')).collect();
+		Bitmap { w: rows[0].len(), h: rows.len(), pixels }
+	}
+
+	#[test]
+	fn reading() {
+		assert_eq!(ocr(&bitmap(TEXT)).unwrap(), "ABCEFGHJKLPRUSZ");
+
+		let mut padded = Robot::new();
+		for (y, row) in TEXT.lines().enumerate() {
+			for (x, c) in row.bytes().enumerate() {
+				padded.map.insert((x as i64 - 3, y as i64 + 2), (c == b'#') as u8);
+			}
+		}
+		padded.map.insert((-10, -10), 0);
+		assert_eq!(ocr(&to_bitmap(&padded.map)).unwrap(), "ABCEFGHJKLPRUSZ");
+
+		assert!(ocr(&bitmap("....\n....")).is_err());
+		assert!(ocr(&bitmap("#...\n#...\n####")).is_err());
+	}
+
+	#[test]
+	fn recording() {
+		let mut brain = Script::from(&[
+			(1, Turn::Left),
+			(0, Turn::Left),
+			(1, Turn::Left),
+			(1, Turn::Left),
+			(0, Turn::Right),
+			(1, Turn::Left),
+			(1, Turn::Left),
+		]);
+		let mut robot = Robot::new();
+		robot.history = Some(Vec::new());
+		robot.run(&mut brain, 0);
+
+		let history = robot.history.as_ref().unwrap();
+		assert_eq!(history.len(), 7);
+		assert_eq!(history[0], Step { pos: (0, 0), dir: Dir::N, color: 1, turn: Turn::Left, fresh: true });
+		assert_eq!(history[4], Step { pos: (0, 0), dir: Dir::N, color: 0, turn: Turn::Right, fresh: false });
+		assert_eq!(history.iter().filter(|s| s.fresh).count() as i64, robot.painted);
+		assert_eq!(bounds(history), (-1, -1, 3, 3));
+
+		let mut frames = Vec::new();
+		let mut last = HashMap::new();
+		replay(history, |map, pos, _| {
+			frames.push(frame_bitmap(map, bounds(history), pos));
+			last = map.clone();
+		});
+		assert_eq!(frames.len(), 8);
+		assert_eq!(last, robot.map);
+		assert_eq!(frames[0].pixels, [false, false, false, false, true, false, false, false, false]);
+		assert_eq!(frames[1].pixels, [false, false, false, true, true, false, false, false, false]);
+	}
+
+	#[test]
+	fn repaint() {
+		let mut robot = Robot::new();
+		robot.step(1, Turn::Left);
+		robot.step(1, Turn::Left);
+		robot.step(1, Turn::Left);
+		robot.step(1, Turn::Left);
+		robot.step(0, Turn::Left);
+		assert_eq!(robot.painted, 4);
+		assert_eq!(robot.map[&(0, 0)], 0);
+	}
+}
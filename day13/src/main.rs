@@ -1,5 +1,9 @@
-use std::io::{self, Read};
+use std::io::{self, Read, Write};
 use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::env;
+use std::fs::{self, File};
+use std::process::Command;
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Result<T, E = Error> = std::result::Result<T, E>;
@@ -20,82 +24,692 @@ fn main() -> Result<()> {
 
 	let mut rom = parse(&input)?;
 
-	println!("p1: {}", solve_part1(&rom));
-	println!("p2: {}", solve_part2(&mut rom));
+	let args: Vec<String> = env::args().skip(1).collect();
+	let args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+	match args[..] {
+		[] => {
+			println!("p1: {}", solve_part1(&rom)?);
+			println!("p2: {}", solve_part2(&mut rom)?);
+		},
+		["auto", path] => {
+			rom[0] = 2;
+			let game = play(&rom, autopilot, false)?;
+			fs::write(path, encode_inputs(&game.inputs))?;
+			println!("score: {} after {} inputs", game.arcade.screen.score, game.inputs.len());
+		},
+		["play", path] => {
+			rom[0] = 2;
+			let mut tty = RawTty::open()?;
+			let mut failure = None;
+			let game = play(&rom, |_| match tty.read_joystick() {
+				Ok(x) => x,
+				Err(e) => {
+					failure = Some(e);
+					None
+				},
+			}, true)?;
+			drop(tty);
+			if let Some(e) = failure {
+				return Err(e);
+			}
+			fs::write(path, encode_inputs(&game.inputs))?;
+			println!("score: {} after {} inputs", game.arcade.screen.score, game.inputs.len());
+		},
+		["replay", path] => {
+			rom[0] = 2;
+			let recording = decode_inputs(&fs::read_to_string(path)?)?;
+			let mut next = recording.iter().cloned();
+			let game = play(&rom, |_| next.next(), true)?;
+			println!("score: {} after {} inputs", game.arcade.screen.score, game.inputs.len());
+		},
+		["compare"] => {
+			rom[0] = 2;
+			let reactive = benchmark(&rom, autopilot)?;
+			let predictor = Predictor::new();
+			let predictive = benchmark(&rom, |a| predictor.steer(a))?;
+			for (name, stats) in [("reactive", reactive), ("predictive", predictive)].iter() {
+				let cleared = match stats.cleared {
+					Some(f) => format!("cleared in {} frames", f),
+					None => "not cleared".to_string(),
+				};
+				println!("{}: score {}, {}, {} moves in {} inputs, {} instructions", name, stats.score, cleared, stats.moves, stats.inputs, stats.instructions);
+			}
+
+			let search = search(&rom)?;
+			search.game.arcade.screen.dump();
+			println!("search: score {}, cleared in {} frames, {} instructions on the chosen line, {} in total",
+				search.game.arcade.screen.score,
+				search.game.inputs.len() + 1,
+				search.game.machine.executed,
+				search.instructions);
+		},
+		_ => return Err("usage: day13 [auto FILE | play FILE | replay FILE | compare] < ROM".into()),
+	}
 	
 	Ok(())
 }
 
-fn solve_part1(rom: &[isize]) -> usize {
+fn solve_part1(rom: &[isize]) -> Result<usize> {
 	let mut m = Machine::from(&rom);
 	let mut output = Vec::new();
 	let s = m.run(&mut output);
 	assert_eq!(s, State::Halted);
 
-	output.chunks_exact(3).filter(|i| i[2] == 2).count()
+	let mut screen = Screen::new();
+	screen.update(&output)?;
+	Ok(screen.count(Tile::Block))
 }
 
-fn solve_part2(rom: &mut [isize]) -> isize {
+fn solve_part2(rom: &mut [isize]) -> Result<isize> {
 	rom[0] = 2;
 
-	let mut m = Machine::from(&rom);
-	let mut output = Vec::new();
-	let mut score = 0;
+	let game = play(rom, autopilot, false)?;
+	Ok(game.arcade.screen.score)
+}
 
-	const H: usize = 22;
-	const W: usize = 40;
-	let mut display = [' '; W * H];
+#[derive(PartialOrd, Ord, PartialEq, Eq, Clone, Copy, Debug)]
+enum Tile {
+	Empty,
+	Wall,
+	Block,
+	Paddle,
+	Ball,
+}
 
-	loop {
-		let s = m.run(&mut output);
+impl Tile {
+	fn from(id: isize) -> Result<Tile> {
+		Ok(match id {
+			0 => Tile::Empty,
+			1 => Tile::Wall,
+			2 => Tile::Block,
+			3 => Tile::Paddle,
+			4 => Tile::Ball,
+			_ => return Err(format!("unsupported tile {}", id).into()),
+		})
+	}
+
+	fn glyph(self) -> char {
+		match self {
+			Tile::Empty => ' ',
+			Tile::Wall => '█',
+			Tile::Block => '▒',
+			Tile::Paddle => '_',
+			Tile::Ball => '○',
+		}
+	}
+}
+
+// A single tile a frame changed.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+struct Change {
+	pos: (usize, usize),
+	old: Tile,
+	new: Tile,
+}
+
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+struct Frame {
+	changes: Vec<Change>,
+	score: Option<isize>,
+}
+
+// Tiles seen so far, the size grows to fit whatever the game draws.
+#[derive(Clone)]
+struct Screen {
+	w: usize,
+	h: usize,
+	tiles: Vec<Tile>,
+	score: isize,
+}
+
+impl Screen {
+	fn new() -> Screen {
+		Screen {
+			w: 0,
+			h: 0,
+			tiles: Vec::new(),
+			score: 0,
+		}
+	}
+
+	// Applies `(x, y, tile)` triples, `(-1, 0, score)` updates the
+	// score segment.
+	fn update(&mut self, output: &[isize]) -> Result<Frame> {
+		if !output.len().is_multiple_of(3) {
+			return Err("incomplete draw command".into());
+		}
 
-		let mut ball = (0, 0);
-		let mut paddle = (0, 0);
+		let mut score = None;
+		let mut draws = Vec::with_capacity(output.len() / 3);
 		for c in output.chunks_exact(3) {
 			if c[0] == -1 && c[1] == 0 {
-				score = c[2];
-			} else {
-				let x = c[0] as usize;
-				let y = c[1] as usize;
-				match c[2] {
-					0 => display[y * W + x] = ' ',
-					1 => display[y * W + x] = '█',
-					2 => display[y * W + x] = '▒',
-					3 => display[y * W + x] = '_',
-					4 => display[y * W + x] = '○',
-					_ => panic!("unsupported tile"),
-				};
-				if c[2] == 3 {
-					paddle = (x, y);
-				}
-				else if c[2] == 4 {
-					ball = (x, y);
-				}
+				score = Some(c[2]);
+				continue;
+			}
+			if c[0] < 0 || c[1] < 0 {
+				return Err(format!("tile out of screen at {},{}", c[0], c[1]).into());
 			}
+			draws.push(((c[0] as usize, c[1] as usize), Tile::from(c[2])?));
 		}
 
-		if s == State::Halted {
-			break;
+		// The whole frame is good, only now the screen changes.
+		let mut frame = Frame { changes: Vec::new(), score };
+		if let Some(score) = score {
+			self.score = score;
 		}
-		
-		let input = match ball.0.cmp(&paddle.0) {
+		for (pos, new) in draws {
+			self.fit(pos);
+			let i = pos.1 * self.w + pos.0;
+			let old = self.tiles[i];
+			if old != new {
+				self.tiles[i] = new;
+				frame.changes.push(Change { pos, old, new });
+			}
+		}
+
+		Ok(frame)
+	}
+
+	fn fit(&mut self, pos: (usize, usize)) {
+		if pos.0 < self.w && pos.1 < self.h {
+			return;
+		}
+
+		let w = self.w.max(pos.0 + 1);
+		let h = self.h.max(pos.1 + 1);
+		let mut tiles = vec![Tile::Empty; w * h];
+		for y in 0..self.h {
+			tiles[y * w..y * w + self.w].copy_from_slice(&self.tiles[y * self.w..(y + 1) * self.w]);
+		}
+		self.w = w;
+		self.h = h;
+		self.tiles = tiles;
+	}
+
+	fn get(&self, pos: (usize, usize)) -> Tile {
+		if pos.0 < self.w && pos.1 < self.h {
+			self.tiles[pos.1 * self.w + pos.0]
+		} else {
+			Tile::Empty
+		}
+	}
+
+	fn count(&self, tile: Tile) -> usize {
+		self.tiles.iter().filter(|&&t| t == tile).count()
+	}
+
+	fn dump(&self) {
+		for y in 0..self.h {
+			for x in 0..self.w {
+				print!("{}", self.get((x, y)).glyph());
+			}
+			println!();
+		}
+	}
+}
+
+#[derive(Clone)]
+struct Arcade {
+	screen: Screen,
+	frame: Frame,
+	ball: Option<(usize, usize)>,
+	// Where the ball was before it last moved.
+	last_ball: Option<(usize, usize)>,
+	paddle: Option<(usize, usize)>,
+}
+
+impl Arcade {
+	fn new() -> Arcade {
+		Arcade {
+			screen: Screen::new(),
+			frame: Frame::default(),
+			ball: None,
+			last_ball: None,
+			paddle: None,
+		}
+	}
+
+	fn update(&mut self, output: &[isize]) -> Result<()> {
+		self.frame = self.screen.update(output)?;
+		let ball = self.ball;
+		for c in self.frame.changes.iter() {
+			match c.new {
+				Tile::Paddle => self.paddle = Some(c.pos),
+				Tile::Ball => self.ball = Some(c.pos),
+				_ => (),
+			}
+		}
+		if self.ball != ball {
+			self.last_ball = ball;
+		}
+		Ok(())
+	}
+
+	fn ball_velocity(&self) -> Option<(isize, isize)> {
+		match (self.last_ball, self.ball) {
+			(Some(a), Some(b)) => Some((b.0 as isize - a.0 as isize, b.1 as isize - a.1 as isize)),
+			_ => None,
+		}
+	}
+
+	// Redraws only the tiles changed by the last frame, raw terminal
+	// mode needs explicit carriage returns.
+	fn render(&self, first: bool) {
+		let mut out = String::new();
+		if first {
+			out.push_str("\x1b[H\x1b[2J");
+		}
+		out.push_str(&format!("\x1b[1;1Hscore: {}\x1b[K", self.screen.score));
+		for c in self.frame.changes.iter() {
+			out.push_str(&format!("\x1b[{};{}H{}", c.pos.1 + 2, c.pos.0 + 1, c.new.glyph()));
+		}
+		out.push_str(&format!("\x1b[{};1H\r\n", self.screen.h + 2));
+		print!("{}", out);
+		let _ = io::stdout().flush();
+	}
+}
+
+fn autopilot(arcade: &Arcade) -> Option<isize> {
+	let (ball, paddle) = match (arcade.ball, arcade.paddle) {
+		(Some(b), Some(p)) => (b, p),
+		_ => return Some(0),
+	};
+	Some(match ball.0.cmp(&paddle.0) {
+		Ordering::Less => -1,
+		Ordering::Equal => 0,
+		Ordering::Greater => 1,
+	})
+}
+
+// Uses the ball velocity between frames and parks the paddle where
+// the ball is going to come down, so it only moves when it has to.
+struct Predictor {
+	// Column shift from the landing point, aiming off by one makes
+	// the ball bounce back off the paddle corner.
+	offset: isize,
+}
+
+impl Predictor {
+	fn new() -> Predictor {
+		Predictor::with_offset(0)
+	}
+
+	fn with_offset(offset: isize) -> Predictor {
+		Predictor { offset }
+	}
+
+	fn steer(&self, arcade: &Arcade) -> Option<isize> {
+		let (ball, paddle) = match (arcade.ball, arcade.paddle) {
+			(Some(b), Some(p)) => (b, p),
+			_ => return Some(0),
+		};
+
+		let vel = arcade.ball_velocity().unwrap_or((0, 0));
+		let target = if vel.0 == 0 || vel.1 == 0 {
+			ball.0 as isize
+		} else {
+			predict(&arcade.screen, ball, vel, paddle.1).unwrap_or(ball.0) as isize + self.offset
+		};
+
+		Some(match target.cmp(&(paddle.0 as isize)) {
 			Ordering::Less => -1,
 			Ordering::Equal => 0,
 			Ordering::Greater => 1,
-		};
+		})
+	}
+}
+
+// Follows the ball bouncing off walls and blocks until it is about to
+// hit the paddle row, returns its column there.
+fn predict(screen: &Screen, ball: (usize, usize), vel: (isize, isize), row: usize) -> Option<usize> {
+	let mut pos = (ball.0 as isize, ball.1 as isize);
+	let mut vel = vel;
+	for _ in 0..screen.w * screen.h * 4 {
+		if vel.1 > 0 && pos.1 + 1 >= row as isize {
+			return Some(pos.0 as usize);
+		}
+		bounce(screen, &mut pos, &mut vel);
+	}
+
+	None
+}
+
+// Frames until the ball hits a block, `None` if it comes back down to
+// the paddle row first.
+fn strike(screen: &Screen, ball: (usize, usize), vel: (isize, isize), row: usize) -> Option<usize> {
+	let mut pos = (ball.0 as isize, ball.1 as isize);
+	let mut vel = vel;
+	for frames in 1..=screen.w * screen.h * 4 {
+		if bounce(screen, &mut pos, &mut vel) {
+			return Some(frames);
+		}
+		if vel.1 > 0 && pos.1 + 1 >= row as isize {
+			return None;
+		}
+	}
+
+	None
+}
+
+// Moves the ball by one frame, returns whether it bounced off a block.
+fn bounce(screen: &Screen, pos: &mut (isize, isize), vel: &mut (isize, isize)) -> bool {
+	let tile = |x: isize, y: isize| {
+		if x < 0 || y < 0 {
+			Tile::Wall
+		} else {
+			screen.get((x as usize, y as usize))
+		}
+	};
+	let solid = |x: isize, y: isize| matches!(tile(x, y), Tile::Wall | Tile::Block);
+
+	let (x, y) = *pos;
+	let (mut dx, mut dy) = *vel;
+	let mut block = false;
+	let mut bounced = false;
+	if solid(x + dx, y) {
+		block |= tile(x + dx, y) == Tile::Block;
+		dx = -dx;
+		bounced = true;
+	}
+	if solid(x, y + dy) {
+		block |= tile(x, y + dy) == Tile::Block;
+		dy = -dy;
+		bounced = true;
+	}
+	if !bounced && solid(x + dx, y + dy) {
+		block |= tile(x + dx, y + dy) == Tile::Block;
+		dx = -dx;
+		dy = -dy;
+	}
+	*vel = (dx, dy);
+	if !solid(x + dx, y + dy) {
+		*pos = (x + dx, y + dy);
+	}
+	block
+}
+
+struct Stats {
+	score: isize,
+	inputs: usize,
+	instructions: usize,
+	// Inputs that actually moved the paddle.
+	moves: usize,
+	// Frame on which the last block was broken.
+	cleared: Option<usize>,
+}
+
+fn benchmark(rom: &[isize], mut joystick: impl FnMut(&Arcade) -> Option<isize>) -> Result<Stats> {
+	let mut frames = 0;
+	let mut cleared = None;
+	let game = play(rom, |a| {
+		frames += 1;
+		if cleared.is_none() && a.screen.count(Tile::Block) == 0 {
+			cleared = Some(frames);
+		}
+		joystick(a)
+	}, false)?;
+	if cleared.is_none() && game.arcade.screen.count(Tile::Block) == 0 {
+		cleared = Some(frames + 1);
+	}
+
+	Ok(Stats {
+		score: game.arcade.screen.score,
+		inputs: game.inputs.len(),
+		instructions: game.machine.executed,
+		moves: game.inputs.iter().filter(|&&x| x != 0).count(),
+		cleared,
+	})
+}
+
+#[derive(Clone)]
+struct Game {
+	machine: Machine,
+	output: Vec<isize>,
+	arcade: Arcade,
+	inputs: Vec<isize>,
+}
+
+impl Game {
+	fn new(rom: &[isize]) -> Game {
+		Game {
+			machine: Machine::from(rom),
+			output: Vec::new(),
+			arcade: Arcade::new(),
+			inputs: Vec::new(),
+		}
+	}
+
+	// Runs up to the next joystick read and draws the frame.
+	fn advance(&mut self) -> Result<State> {
+		self.output.clear();
+		let s = self.machine.run(&mut self.output);
+		self.arcade.update(&self.output)?;
+		Ok(s)
+	}
+
+	fn feed(&mut self, input: isize) {
+		self.machine.feed(input);
+		self.inputs.push(input);
+	}
+
+	fn lost(&self) -> bool {
+		match self.machine.state {
+			State::Halted => self.arcade.screen.count(Tile::Block) > 0,
+			State::Stalled => true,
+			_ => false,
+		}
+	}
+}
+
+// Frames the ball may fly without coming down on the paddle before the
+// line is given up.
+const HORIZON: usize = 5000;
+// Instructions a single frame may take before the game is considered
+// stuck.
+const FRAME_FUEL: usize = 10_000;
+// Frames before the ball comes down on the paddle at which the other
+// way of catching it branches off, enough to shift the paddle by a
+// column.
+const LEAD: usize = 1;
+
+struct Search {
+	game: Game,
+	// Instructions spent on all lines, including abandoned ones.
+	instructions: usize,
+}
+
+// Where the ball came down on the paddle.
+struct Catch {
+	// The game `LEAD` frames before.
+	game: Game,
+	ball: (usize, usize),
+	vel: (isize, isize),
+	row: usize,
+}
+
+// Plays the predictor and snapshots the game every time the ball comes
+// down on the paddle. Catching it with the paddle corner instead sends
+// it back the way it came, so at every catch the ball model is run
+// both ways and the game goes on with the one that hits a block
+// sooner. The other one is kept untried and only played out when the
+// ball is lost, so the emulator runs little besides the line it
+// picks.
+fn search(rom: &[isize]) -> Result<Search> {
+	let mut game = Game::new(rom);
+	game.advance()?;
+	let mut instructions = game.machine.executed;
 
-		m.feed(input);
-		output.clear();
+	// Snapshots of the catches not taken with the paddle offset to take
+	// them, latest last.
+	let mut untried: Vec<(Game, isize)> = Vec::new();
+	let mut offset = 0;
+	loop {
+		let start = game.machine.executed;
+		let catch = fast_forward(&mut game, offset)?;
+		instructions += game.machine.executed - start;
+		offset = 0;
+
+		match catch {
+			Some(catch) => {
+				let (dx, dy) = catch.vel;
+				let screen = &catch.game.arcade.screen;
+				let corner = strike(screen, catch.ball, (-dx, -dy), catch.row);
+				let straight = strike(screen, catch.ball, (dx, -dy), catch.row);
+				// The ball only comes back off the corner it flies into.
+				if corner.is_some_and(|c| straight.is_none_or(|s| c < s)) {
+					untried.push((game, 0));
+					game = catch.game;
+					offset = dx;
+				} else {
+					untried.push((catch.game, dx));
+				}
+			},
+			None if game.machine.state == State::Halted && !game.lost() => break,
+			None => {
+				let (g, o) = untried.pop().ok_or("every line loses the ball")?;
+				game = g;
+				offset = o;
+			},
+		}
 	}
 
-	for y in 0..H {
-		for x in 0..W {
-			print!("{}", display[y * W + x]);
+	Ok(Search { game, instructions })
+}
+
+// Steers the paddle until the ball comes down on it or the game is
+// over. A line branched off `LEAD` frames before a catch aims `offset`
+// columns off for its first `LEAD + 1` frames, up to the bounce off the
+// paddle, and only stops at catches after those.
+fn fast_forward(game: &mut Game, offset: isize) -> Result<Option<Catch>> {
+	let mut recent = VecDeque::new();
+	for frames in 1..=HORIZON {
+		recent.push_back(game.clone());
+		if recent.len() > LEAD {
+			recent.pop_front();
+		}
+
+		let predictor = Predictor::with_offset(if frames <= LEAD + 1 { offset } else { 0 });
+		let input = predictor.steer(&game.arcade).unwrap_or(0);
+		game.feed(input);
+		game.machine.fuel = Some(FRAME_FUEL);
+		if game.advance()? != State::NeedsInput {
+			return Ok(None);
 		}
-		println!();
+
+		if let (Some(b), Some(p), Some(vel)) = (game.arcade.ball, game.arcade.paddle, game.arcade.ball_velocity()) {
+			if frames > LEAD + 1 && b.1 + 1 == p.1 && vel.1 > 0 {
+				return Ok(recent.pop_front().map(|g| Catch { game: g, ball: b, vel, row: p.1 }));
+			}
+		}
+	}
+	Ok(None)
+}
+
+// Runs the game until it halts or the joystick gives up.
+fn play(rom: &[isize], mut joystick: impl FnMut(&Arcade) -> Option<isize>, live: bool) -> Result<Game> {
+	let mut game = Game::new(rom);
+
+	loop {
+		let s = game.advance()?;
+		if live {
+			game.arcade.render(game.inputs.is_empty());
+		}
+
+		if s == State::Halted {
+			break;
+		}
+
+		let input = match joystick(&game.arcade) {
+			Some(x) => x,
+			None => break,
+		};
+
+		game.feed(input);
+	}
+
+	if !live {
+		game.arcade.screen.dump();
 	}
 	
-	score
+	Ok(game)
+}
+
+// Recordings keep one character per joystick input.
+fn encode_inputs(inputs: &[isize]) -> String {
+	let mut s: String = inputs.iter().map(|&x| match x {
+		-1 => '<',
+		1 => '>',
+		_ => '.',
+	}).collect();
+	s.push('\n');
+	s
+}
+
+fn decode_inputs(s: &str) -> Result<Vec<isize>> {
+	s.trim().chars().map(|c| match c {
+		'<' => Ok(-1),
+		'.' => Ok(0),
+		'>' => Ok(1),
+		_ => Err(format!("unexpected joystick input '{}'", c).into()),
+	}).collect()
+}
+
+// Terminal switched into raw mode via `stty`, restored on drop.
+struct RawTty {
+	tty: File,
+	saved: String,
+}
+
+impl RawTty {
+	fn open() -> Result<RawTty> {
+		let tty = File::open("/dev/tty")?;
+		let saved = stty(&tty, &["-g"])?;
+		stty(&tty, &["raw", "-echo"])?;
+		Ok(RawTty { tty, saved: saved.trim().to_string() })
+	}
+
+	// Left and right arrows move the paddle, `q` quits and any other
+	// key keeps the paddle in place.
+	fn read_joystick(&mut self) -> Result<Option<isize>> {
+		Ok(match self.read_byte()? {
+			b'q' | 3 => None,
+			0x1b => {
+				if self.read_byte()? != b'[' {
+					return Ok(Some(0));
+				}
+				match self.read_byte()? {
+					b'D' => Some(-1),
+					b'C' => Some(1),
+					_ => Some(0),
+				}
+			},
+			_ => Some(0),
+		})
+	}
+
+	fn read_byte(&mut self) -> Result<u8> {
+		let mut b = [0];
+		self.tty.read_exact(&mut b)?;
+		Ok(b[0])
+	}
+}
+
+impl Drop for RawTty {
+	fn drop(&mut self) {
+		let saved = self.saved.clone();
+		let _ = stty(&self.tty, &[&saved]);
+	}
+}
+
+fn stty(tty: &File, args: &[&str]) -> Result<String> {
+	let output = Command::new("stty")
+		.args(args)
+		.stdin(tty.try_clone()?)
+		.output()?;
+	if !output.status.success() {
+		return Err("stty failed".into());
+	}
+	Ok(String::from_utf8(output.stdout)?)
 }
 
 fn parse(input: &str) -> Result<Box<[isize]>> {
@@ -108,6 +722,7 @@ fn parse(input: &str) -> Result<Box<[isize]>> {
 	Ok(ram.into_boxed_slice())
 }
 
+#[derive(Clone)]
 struct Machine {
 	ram: Vec<isize>,
 	pc: usize,
@@ -116,6 +731,13 @@ struct Machine {
 
 	input: Vec<isize>,
 	consumed: usize,
+
+	// Instructions dispatched so far, a read waiting for input counts
+	// again once resumed.
+	executed: usize,
+	// Instructions left before giving up, some inputs make the game
+	// spin forever.
+	fuel: Option<usize>,
 }
 
 impl Machine {
@@ -127,6 +749,8 @@ impl Machine {
 			state: State::Ready,
 			input: Vec::new(),
 			consumed: 0,
+			executed: 0,
+			fuel: None,
 		}
 	}
 
@@ -164,12 +788,21 @@ impl Machine {
 	}
 	
 	fn run(&mut self, output: &mut Vec<isize>) -> State {
-		if self.state == State::Halted {
+		if self.state == State::Halted || self.state == State::Stalled {
 			return self.state;
 		}
 
 		loop {
 			let (op, len) = decode(&self.ram[self.pc..]);
+			if let Some(fuel) = self.fuel {
+				if fuel == 0 {
+					trace!(";; stalled, out of fuel");
+					self.state = State::Stalled;
+					break;
+				}
+				self.fuel = Some(fuel - 1);
+			}
+			self.executed += 1;
 
 			trace!("{:?}", op);
 			
@@ -255,6 +888,7 @@ enum State {
 	Ready = 0,
 	NeedsInput,
 	Halted,
+	Stalled,
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
@@ -375,8 +1009,366 @@ fn decode_param(mode: u8, value: isize) -> Param {
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use std::collections::HashMap;
 
 	#[test]
 	fn examples() {
 	}
+
+	#[test]
+	fn recording() {
+		let inputs = [-1, 0, 0, 1, -1];
+		let s = encode_inputs(&inputs);
+		assert_eq!(s, "<..><\n");
+		assert_eq!(decode_inputs(&s).unwrap(), inputs);
+		assert!(decode_inputs("<x>").is_err());
+	}
+
+	#[test]
+	fn screen() {
+		let mut screen = Screen::new();
+		let frame = screen.update(&[1, 2, 3, -1, 0, 12, 6, 5, 4]).unwrap();
+		assert_eq!((screen.w, screen.h, screen.score), (7, 6, 12));
+		assert_eq!(frame.score, Some(12));
+		assert_eq!(frame.changes, [
+			Change { pos: (1, 2), old: Tile::Empty, new: Tile::Paddle },
+			Change { pos: (6, 5), old: Tile::Empty, new: Tile::Ball },
+		]);
+		assert_eq!(screen.get((1, 2)), Tile::Paddle);
+		assert_eq!(screen.get((100, 100)), Tile::Empty);
+
+		let frame = screen.update(&[1, 2, 0, 9, 0, 2]).unwrap();
+		assert_eq!((screen.w, screen.h), (10, 6));
+		assert_eq!(frame.score, None);
+		assert_eq!(frame.changes[0], Change { pos: (1, 2), old: Tile::Paddle, new: Tile::Empty });
+		assert_eq!(screen.get((6, 5)), Tile::Ball);
+		assert_eq!(screen.count(Tile::Block), 1);
+
+		assert!(screen.update(&[0, 0, 5]).is_err());
+		assert!(screen.update(&[0, -2, 1]).is_err());
+		assert!(screen.update(&[0, 0]).is_err());
+
+		// Drawing what is already there changes nothing.
+		let frame = screen.update(&[6, 5, 4, 9, 0, 2, 0, 0, 1]).unwrap();
+		assert_eq!(frame.changes, [Change { pos: (0, 0), old: Tile::Empty, new: Tile::Wall }]);
+
+		// A bad triple anywhere leaves the whole frame undrawn.
+		assert!(screen.update(&[-1, 0, 99, 20, 20, 1, 0, 0, 7]).is_err());
+		assert_eq!((screen.w, screen.h, screen.score), (10, 6, 12));
+		assert_eq!(screen.get((0, 0)), Tile::Wall);
+	}
+
+	#[test]
+	fn replay() {
+		// Shows the score 7, then reads the joystick and shows it as
+		// the new score.
+		let rom = parse("104,-1,104,0,104,7,3,100,104,-1,104,0,4,100,99").unwrap();
+		let game = play(&rom, |a| {
+			assert_eq!(a.screen.score, 7);
+			Some(-1)
+		}, false).unwrap();
+		assert_eq!((game.arcade.screen.score, &game.inputs[..]), (-1, &[-1][..]));
+		assert_eq!(game.machine.executed, 9);
+
+		let game = play(&rom, |_| None, false).unwrap();
+		assert_eq!((game.arcade.screen.score, game.inputs.len()), (7, 0));
+	}
+
+	#[test]
+	fn fuel() {
+		let rom = parse("1105,1,0").unwrap();
+		let mut m = Machine::from(&rom);
+		m.fuel = Some(10);
+		assert_eq!(m.run(&mut Vec::new()), State::Stalled);
+		assert_eq!(m.executed, 10);
+		assert_eq!(m.run(&mut Vec::new()), State::Stalled);
+	}
+
+	#[test]
+	fn prediction() {
+		let mut output = Vec::new();
+		for y in 0..8 {
+			output.extend_from_slice(&[0, y, 1, 9, y, 1]);
+		}
+		for x in 0..10 {
+			output.extend_from_slice(&[x, 0, 1]);
+		}
+		output.extend_from_slice(&[4, 7, 3]);
+		let mut screen = Screen::new();
+		screen.update(&output).unwrap();
+
+		assert_eq!(predict(&screen, (2, 2), (1, 1), 7), Some(6));
+		assert_eq!(predict(&screen, (7, 4), (1, 1), 7), Some(7));
+		assert_eq!(predict(&screen, (3, 2), (-1, -1), 7), Some(5));
+	}
+
+	// Intcode assembler for the test ROMs below. Operands name variables
+	// that live after the code, or labels.
+	enum Arg {
+		Imm(isize),
+		Var(&'static str),
+		// A label's address as an immediate.
+		Addr(String),
+		// Writes into the cell at a label.
+		Into(String),
+		// An operand the code patches, the label is put on its cell.
+		Slot(String),
+	}
+
+	enum Word {
+		Num(isize),
+		Var(&'static str),
+		Label(String),
+	}
+
+	const ADD: isize = 1;
+	const MUL: isize = 2;
+	const IN: isize = 3;
+	const OUT: isize = 4;
+	const JT: isize = 5;
+	const JF: isize = 6;
+	const LT: isize = 7;
+	const EQ: isize = 8;
+	const HALT: isize = 99;
+
+	fn v(name: &'static str) -> Arg {
+		Arg::Var(name)
+	}
+
+	fn n(x: isize) -> Arg {
+		Arg::Imm(x)
+	}
+
+	fn to(label: &str) -> Arg {
+		Arg::Addr(label.to_string())
+	}
+
+	#[derive(Default)]
+	struct Asm {
+		words: Vec<Word>,
+		labels: HashMap<String, usize>,
+		fresh: usize,
+	}
+
+	impl Asm {
+		fn op(&mut self, code: isize, args: &[Arg]) {
+			let at = self.words.len();
+			self.words.push(Word::Num(0));
+			let mut modes = 0;
+			let mut mode = 100;
+			for arg in args {
+				let word = match arg {
+					Arg::Imm(x) => {
+						modes += mode;
+						Word::Num(*x)
+					},
+					Arg::Var(name) => Word::Var(name),
+					Arg::Addr(label) => {
+						modes += mode;
+						Word::Label(label.clone())
+					},
+					Arg::Into(label) => Word::Label(label.clone()),
+					Arg::Slot(label) => {
+						self.label(label);
+						Word::Num(0)
+					},
+				};
+				self.words.push(word);
+				mode *= 10;
+			}
+			self.words[at] = Word::Num(code + modes);
+		}
+
+		fn label(&mut self, name: &str) {
+			self.labels.insert(name.to_string(), self.words.len());
+		}
+
+		fn fresh(&mut self) -> String {
+			self.fresh += 1;
+			format!("_{}", self.fresh)
+		}
+
+		fn out3(&mut self, x: Arg, y: Arg, z: Arg) {
+			self.op(OUT, &[x]);
+			self.op(OUT, &[y]);
+			self.op(OUT, &[z]);
+		}
+
+		// Points `a` at the board cell of `x` and `y`.
+		fn index(&mut self, width: isize, x: Arg, y: Arg) {
+			self.op(MUL, &[y, n(width), v("a")]);
+			self.op(ADD, &[v("a"), x, v("a")]);
+			self.op(ADD, &[v("a"), to("board"), v("a")]);
+		}
+
+		// Reads the cell `a` points at into `t`.
+		fn load(&mut self) {
+			let slot = self.fresh();
+			self.op(ADD, &[v("a"), n(0), Arg::Into(slot.clone())]);
+			self.op(ADD, &[Arg::Slot(slot), n(0), v("t")]);
+		}
+
+		fn store(&mut self, x: Arg) {
+			let slot = self.fresh();
+			self.op(ADD, &[v("a"), n(0), Arg::Into(slot.clone())]);
+			self.op(ADD, &[x, n(0), Arg::Slot(slot)]);
+		}
+
+		// Lays the variables and then the board out after the code.
+		fn link(mut self, vars: &[(&'static str, isize)], board: &[isize]) -> Vec<isize> {
+			let code = self.words.len();
+			self.labels.insert("board".to_string(), code + vars.len());
+			let mut rom: Vec<isize> = self.words.iter().map(|w| match w {
+				Word::Num(x) => *x,
+				Word::Var(name) => (code + vars.iter().position(|(v, _)| v == name).unwrap()) as isize,
+				Word::Label(label) => self.labels[label] as isize,
+			}).collect();
+			rom.extend(vars.iter().map(|&(_, x)| x));
+			rom.extend_from_slice(board);
+			rom
+		}
+	}
+
+	// Breakout on a board of `#` walls, `x` blocks worth a point each and
+	// a `=` paddle on the second to last row. Every frame reads the
+	// joystick, moves the paddle, then moves the ball down and right from
+	// `ball` the way `bounce` does, breaking the blocks it bounces off.
+	// Halts once the ball drops below the paddle or the blocks are gone.
+	fn breakout(rows: &[&str], ball: (isize, isize)) -> Vec<isize> {
+		let w = rows[0].len() as isize;
+		let row = rows.len() as isize - 2;
+		let board: Vec<isize> = rows.iter().flat_map(|r| r.bytes()).map(|c| match c {
+			b'#' => 1,
+			b'x' => 2,
+			b'=' => 3,
+			_ => 0,
+		}).collect();
+		let paddle = rows[row as usize].find('=').unwrap() as isize;
+
+		let mut a = Asm::default();
+		// `compare` puts a 2 in the first cell for free play, which only
+		// turns this into a multiply.
+		a.op(ADD, &[v("q"), v("q"), v("q")]);
+
+		a.op(ADD, &[n(0), n(0), v("y")]);
+		a.label("draw_row");
+		a.op(ADD, &[n(0), n(0), v("x")]);
+		a.label("draw_cell");
+		a.index(w, v("x"), v("y"));
+		a.load();
+		a.out3(v("x"), v("y"), v("t"));
+		a.op(ADD, &[v("x"), n(1), v("x")]);
+		a.op(LT, &[v("x"), n(w), v("c")]);
+		a.op(JT, &[v("c"), to("draw_cell")]);
+		a.op(ADD, &[v("y"), n(1), v("y")]);
+		a.op(LT, &[v("y"), n(rows.len() as isize), v("c")]);
+		a.op(JT, &[v("c"), to("draw_row")]);
+		a.out3(v("bx"), v("by"), n(4));
+		a.out3(n(-1), n(0), v("score"));
+
+		a.label("frame");
+		a.op(IN, &[v("j")]);
+		a.index(w, v("px"), n(row));
+		a.store(n(0));
+		a.out3(v("px"), n(row), n(0));
+		a.op(ADD, &[v("px"), v("j"), v("px")]);
+		a.op(LT, &[v("px"), n(1), v("c")]);
+		a.op(JF, &[v("c"), to("left")]);
+		a.op(ADD, &[n(1), n(0), v("px")]);
+		a.label("left");
+		a.op(LT, &[v("px"), n(w - 2), v("c")]);
+		a.op(JT, &[v("c"), to("right")]);
+		a.op(ADD, &[n(w - 2), n(0), v("px")]);
+		a.label("right");
+		a.index(w, v("px"), n(row));
+		a.store(n(3));
+		a.out3(v("px"), n(row), n(3));
+
+		// Looks at a cell next to the ball; if anything is there, breaks
+		// it when it is a block and turns the ball.
+		let probe = |a: &mut Asm, x: &'static str, y: &'static str, turn: &dyn Fn(&mut Asm)| {
+			let empty = a.fresh();
+			let wall = a.fresh();
+			a.index(w, v(x), v(y));
+			a.load();
+			a.op(JF, &[v("t"), to(&empty)]);
+			a.op(EQ, &[v("t"), n(2), v("c")]);
+			a.op(JF, &[v("c"), to(&wall)]);
+			a.store(n(0));
+			a.out3(v(x), v(y), n(0));
+			a.op(ADD, &[v("score"), n(1), v("score")]);
+			a.op(ADD, &[v("blocks"), n(-1), v("blocks")]);
+			a.out3(n(-1), n(0), v("score"));
+			a.label(&wall);
+			turn(a);
+			a.label(&empty);
+		};
+		let ahead = |a: &mut Asm| {
+			a.op(ADD, &[v("bx"), v("dx"), v("nx")]);
+			a.op(ADD, &[v("by"), v("dy"), v("ny")]);
+		};
+
+		a.op(ADD, &[n(0), n(0), v("bounced")]);
+		ahead(&mut a);
+		probe(&mut a, "nx", "by", &|a| {
+			a.op(MUL, &[v("dx"), n(-1), v("dx")]);
+			a.op(ADD, &[n(1), n(0), v("bounced")]);
+		});
+		probe(&mut a, "bx", "ny", &|a| {
+			a.op(MUL, &[v("dy"), n(-1), v("dy")]);
+			a.op(ADD, &[n(1), n(0), v("bounced")]);
+		});
+		a.op(JT, &[v("bounced"), to("move")]);
+		ahead(&mut a);
+		probe(&mut a, "nx", "ny", &|a| {
+			a.op(MUL, &[v("dx"), n(-1), v("dx")]);
+			a.op(MUL, &[v("dy"), n(-1), v("dy")]);
+		});
+		a.label("move");
+		ahead(&mut a);
+		a.index(w, v("nx"), v("ny"));
+		a.load();
+		a.op(JT, &[v("t"), to("blocked")]);
+		a.out3(v("bx"), v("by"), n(0));
+		a.op(ADD, &[v("nx"), n(0), v("bx")]);
+		a.op(ADD, &[v("ny"), n(0), v("by")]);
+		a.out3(v("bx"), v("by"), n(4));
+		a.label("blocked");
+		a.op(JF, &[v("blocks"), to("over")]);
+		a.op(LT, &[v("by"), n(row), v("c")]);
+		a.op(JT, &[v("c"), to("frame")]);
+		a.label("over");
+		a.op(HALT, &[]);
+
+		let blocks = board.iter().filter(|&&c| c == 2).count() as isize;
+		a.link(&[
+			("q", 0), ("x", 0), ("y", 0), ("a", 0), ("t", 0), ("c", 0), ("j", 0),
+			("bx", ball.0), ("by", ball.1), ("dx", 1), ("dy", 1), ("px", paddle),
+			("score", 0), ("blocks", blocks), ("bounced", 0), ("nx", 0), ("ny", 0),
+		], &board)
+	}
+
+	#[test]
+	fn searching() {
+		let rom = breakout(&[
+			"#########",
+			"#x..x...#",
+			"#x......#",
+			"#.......#",
+			"#.......#",
+			"#.......#",
+			"#.......#",
+			"#...=...#",
+			"#.......#",
+		], (4, 5));
+		let predictor = Predictor::new();
+		let predictive = benchmark(&rom, |a| predictor.steer(a)).unwrap();
+		assert_eq!((predictive.score, predictive.cleared), (3, Some(61)));
+
+		let search = search(&rom).unwrap();
+		assert_eq!(search.game.arcade.screen.score, 3);
+		assert_eq!(search.game.arcade.screen.count(Tile::Block), 0);
+		assert_eq!(search.game.inputs.len() + 1, 21);
+		assert!(search.instructions < predictive.instructions / 2);
+	}
 }
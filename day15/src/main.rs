@@ -1,5 +1,5 @@
 use std::io::{self, Read};
-use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Result<T, E = Error> = std::result::Result<T, E>;
@@ -19,57 +19,126 @@ fn main() -> Result<()> {
 	io::stdin().read_to_string(&mut input)?;
 
 	let rom = parse(&input)?;
-	let mut drone = Drone::new(&rom);
-	drone.discover();
-	dump(&drone.map, W, H);
+	for &strategy in Strategy::ALL.iter() {
+		let mut drone = Drone::new(Remote::from(&rom));
+		let steps = drone.explore(strategy);
+		println!("{:?}: {} steps", strategy, steps);
+	}
+
+	let mut drone = Drone::new(Remote::from(&rom));
+	drone.explore(Strategy::Frontier);
+	dump(&drone.map);
 
 	let sys = drone.sys.ok_or("no oxygen system point")?;
-	let steps = flood_fill(&drone.map, W, H, sys);
-	let p1 = steps[to_offset((0, 0), W, H)].ok_or("failed to find the path")?;
+	let flood = flood_fill(&drone.map, sys);
+	let p1 = *flood.steps.get(&(0, 0)).ok_or("failed to find the path")?;
 	println!("p1: {}", p1);
 
-	let max = steps.iter().map(|s| s.unwrap_or(0)).max().ok_or("failed to find the path")?;
+	let route = flood.route((0, 0)).ok_or("failed to find the path")?;
+	dump_route(&drone.map, &route);
+	let mut remote = Remote::from(&rom);
+	let end = route.iter().map(|&dir| remote.step(dir)).last();
+	if end != Some(Cell::System) {
+		return Err("route does not lead to the oxygen system".into());
+	}
+
+	dump_spread(&drone.map, &flood);
+	let max = *flood.steps.values().max().ok_or("failed to find the path")?;
 	println!("p2: {}", max);
 	
 	Ok(())
 }
 
-fn flood_fill(map: &[Cell], w: usize, h: usize, start: Pos) -> Box<[Option<usize>]> {
+struct Flood {
+	steps: HashMap<Pos, usize>,
+	// Direction the flood came into the cell from.
+	prev: HashMap<Pos, Dir>,
+}
+
+impl Flood {
+	// Directions leading from `from` back to where the flood started.
+	fn route(&self, from: Pos) -> Option<Vec<Dir>> {
+		let mut route = Vec::new();
+		let mut p = from;
+		self.steps.get(&p)?;
+		while let Some(&dir) = self.prev.get(&p) {
+			let back = dir.rev();
+			route.push(back);
+			p = back.apply(p);
+		}
+		Some(route)
+	}
+}
+
+fn flood_fill(map: &Map, start: Pos) -> Flood {
 	let mut q = VecDeque::new();
 	q.push_back((1, start));
 
-	let mut steps = vec![None; w * h];
-	steps[to_offset(start, w, h)] = Some(1);
+	let mut steps = HashMap::new();
+	let mut prev = HashMap::new();
+	steps.insert(start, 1);
 	
 	while !q.is_empty() {
 		let (s, p) = q.pop_front().unwrap();
 		for &dir in Dir::ALL.iter() {
 			let n = dir.apply(p);
-			let c = map[to_offset(n, w, h)];
-			if steps[to_offset(n, w, h)].is_some() {
+			if steps.contains_key(&n) {
 				continue;
 			}
-			if c == Cell::Wall {
+			// Fog has no edge, the flood only spreads over what is known
+			// to be open.
+			if !matches!(map.get(n), Cell::Empty | Cell::System) {
 				continue;
 			}
-			steps[to_offset(n, w, h)] = Some(s);
+			steps.insert(n, s);
+			prev.insert(n, dir);
 			q.push_back((s + 1, n));
 		}
 	}
 
-	steps.into_boxed_slice()
+	Flood { steps, prev }
+}
+
+fn dump(map: &Map) {
+	render(map, |_| None);
+}
+
+fn dump_route(map: &Map, route: &[Dir]) {
+	let mut marks = HashMap::new();
+	let mut p = (0, 0);
+	for &dir in route {
+		marks.insert(p, match dir {
+			Dir::N => '^',
+			Dir::S => 'v',
+			Dir::W => '<',
+			Dir::E => '>',
+		});
+		p = dir.apply(p);
+	}
+	render(map, |p| marks.get(&p).cloned());
+}
+
+// Every cell shows the last digit of the minute oxygen reached it.
+fn dump_spread(map: &Map, flood: &Flood) {
+	render(map, |p| match (map.get(p), flood.steps.get(&p)) {
+		(Cell::Empty, Some(&s)) => std::char::from_digit((s % 10) as u32, 10),
+		_ => None,
+	});
 }
 
-fn dump(map: &[Cell], w: usize, h: usize) {
-	for y in 0..h {
-		for x in 0..w {
-			let c = match map[y * w + x] {
+fn render(map: &Map, overlay: impl Fn(Pos) -> Option<char>) {
+	let (lo, hi) = map.bounds();
+	for y in lo.1..=hi.1 {
+		for x in lo.0..=hi.0 {
+			let c = match map.get((x, y)) {
 				Cell::Fog => '▒',
 				Cell::Empty => ' ',
 				Cell::Wall => '█',
 				Cell::System => 'x',
 			};
-			if (y * w + x) == to_offset((0, 0), w, h) {
+			if let Some(c) = overlay((x, y)) {
+				print!("{}", c);
+			} else if (x, y) == (0, 0) {
 				print!("D");
 			} else {
 				print!("{}", c);
@@ -79,53 +148,74 @@ fn dump(map: &[Cell], w: usize, h: usize) {
 	}
 }
 
-const W: usize = 45;
-const H: usize = 45;
+// Everything the drone has seen so far, grows with the maze.
+#[derive(PartialEq, Eq, Debug)]
+struct Map {
+	cells: HashMap<Pos, Cell>,
+}
+
+impl Map {
+	fn new() -> Map {
+		Map { cells: HashMap::new() }
+	}
+
+	fn get(&self, p: Pos) -> Cell {
+		self.cells.get(&p).cloned().unwrap_or(Cell::Fog)
+	}
+
+	fn set(&mut self, p: Pos, cell: Cell) {
+		self.cells.insert(p, cell);
+	}
+
+	// Corners of the smallest box around every seen cell.
+	fn bounds(&self) -> (Pos, Pos) {
+		let mut lo = (0, 0);
+		let mut hi = (0, 0);
+		for &(x, y) in self.cells.keys() {
+			lo = (lo.0.min(x), lo.1.min(y));
+			hi = (hi.0.max(x), hi.1.max(y));
+		}
+		(lo, hi)
+	}
+}
+
+#[derive(PartialOrd, Ord, PartialEq, Eq, Clone, Copy, Debug)]
+enum Strategy {
+	// Depth-first walk, physically backtracking every branch.
+	Dfs,
+	// Walks the shortest known path to the nearest fog cell.
+	Frontier,
+	// Left-hand rule, covers every cell of a maze without loops.
+	WallFollow,
+}
+
+impl Strategy {
+	pub const ALL: [Strategy; 3] = [
+		Strategy::Dfs,
+		Strategy::Frontier,
+		Strategy::WallFollow,
+	];
+}
+
+trait Droid {
+	fn step(&mut self, dir: Dir) -> Cell;
+}
 
-struct Drone {
+struct Remote {
 	machine: Machine,
 	output: Vec<isize>,
-	map: [Cell; W * H],
-	pos: Pos,
-	sys: Option<Pos>,
 }
 
-impl Drone {
-	fn new(rom: &[isize]) -> Drone {
-		Drone {
+impl Remote {
+	fn from(rom: &[isize]) -> Remote {
+		Remote {
 			machine: Machine::from(rom),
 			output: Vec::new(),
-			map: [Cell::Fog; W * H],
-			pos: (0, 0),
-			sys: None,
-		}
-	}
-
-	fn discover(&mut self) {
-		for &dir in Dir::ALL.iter() {
-			let p = dir.apply(self.pos);
-			let offset = to_offset(p, W, H);
-			if self.map[offset] == Cell::Fog {
-				let cell = self.step(dir);
-				self.map[offset] = cell;
-				match cell {
-					Cell::Wall => (),
-					Cell::Empty | Cell::System => {
-						if cell == Cell::System {
-							self.sys = Some(p);
-						}
-						self.pos = p;
-						self.discover();
-						let back = dir.rev();
-						self.step(back);
-						self.pos = back.apply(self.pos);
-					},
-					Cell::Fog => unreachable!(),
-				}
-			}
 		}
 	}
+}
 
+impl Droid for Remote {
 	fn step(&mut self, dir: Dir) -> Cell {
 		self.output.clear();
 		self.machine.feed(match dir {
@@ -144,20 +234,166 @@ impl Drone {
 	}
 }
 
-type Pos = (i64, i64);
+struct Drone<D: Droid> {
+	droid: D,
+	map: Map,
+	pos: Pos,
+	sys: Option<Pos>,
+	steps: usize,
+}
 
-fn to_offset(p: Pos, w: usize, h: usize) -> usize {
-	let x = offset(p.0, w);
-	let y = offset(p.1, h);
-	assert!(x < w && y < h);
-	return y * w + x;
-	
-	fn offset(v: i64, dim: usize) -> usize {
-		(v + (dim as i64) / 2) as usize
+impl<D: Droid> Drone<D> {
+	fn new(droid: D) -> Drone<D> {
+		let mut map = Map::new();
+		map.set((0, 0), Cell::Empty);
+		Drone {
+			droid,
+			map,
+			pos: (0, 0),
+			sys: None,
+			steps: 0,
+		}
+	}
+
+	// Uncovers every reachable cell, returns the amount of commands
+	// sent to the droid.
+	fn explore(&mut self, strategy: Strategy) -> usize {
+		match strategy {
+			Strategy::Dfs => self.explore_dfs(),
+			Strategy::Frontier => self.explore_frontier(),
+			Strategy::WallFollow => self.explore_wall_follow(),
+		}
+		self.steps
+	}
+
+	fn explore_dfs(&mut self) {
+		// Every frame is the direction we came from and the next
+		// direction to try.
+		let mut stack: Vec<(Option<Dir>, usize)> = vec![(None, 0)];
+		while let Some(top) = stack.last_mut() {
+			if top.1 < Dir::ALL.len() {
+				let dir = Dir::ALL[top.1];
+				top.1 += 1;
+				if self.look(dir) == Cell::Fog && self.probe(dir) != Cell::Wall {
+					stack.push((Some(dir), 0));
+				}
+			} else {
+				if let Some(dir) = top.0 {
+					self.go(dir.rev());
+				}
+				stack.pop();
+			}
+		}
+	}
+
+	fn explore_frontier(&mut self) {
+		while let Some(path) = self.path_to_fog() {
+			let (&last, path) = path.split_last().unwrap();
+			for &dir in path {
+				self.go(dir);
+			}
+			self.probe(last);
+		}
+	}
+
+	fn explore_wall_follow(&mut self) {
+		let mut heading = Dir::N;
+		// Being at the same place with the same heading without
+		// learning anything new in between means we are looping.
+		let mut seen = HashSet::new();
+		while seen.insert((self.pos, heading)) {
+			let turns = [heading.turn_left(), heading, heading.turn_right(), heading.rev()];
+			for &dir in turns.iter() {
+				let known = self.look(dir);
+				if known == Cell::Wall {
+					continue;
+				}
+				if known == Cell::Fog {
+					seen.clear();
+					if self.probe(dir) == Cell::Wall {
+						continue;
+					}
+				} else {
+					self.go(dir);
+				}
+				heading = dir;
+				break;
+			}
+		}
+	}
+
+	// Shortest path through the known cells, ending with a step into
+	// the nearest fog.
+	fn path_to_fog(&self) -> Option<Vec<Dir>> {
+		let mut prev = HashMap::new();
+		let mut q = VecDeque::new();
+		q.push_back(self.pos);
+		prev.insert(self.pos, (Dir::N, self.pos));
+
+		while let Some(p) = q.pop_front() {
+			for &dir in Dir::ALL.iter() {
+				let n = dir.apply(p);
+				let cell = self.map.get(n);
+				if prev.contains_key(&n) || cell == Cell::Wall {
+					continue;
+				}
+				prev.insert(n, (dir, p));
+				if cell == Cell::Fog {
+					let mut path = Vec::new();
+					let mut at = n;
+					while at != self.pos {
+						let (dir, from) = prev[&at];
+						path.push(dir);
+						at = from;
+					}
+					path.reverse();
+					return Some(path);
+				}
+				q.push_back(n);
+			}
+		}
+
+		None
+	}
+
+	fn look(&self, dir: Dir) -> Cell {
+		self.map.get(dir.apply(self.pos))
+	}
+
+	// Steps into an unknown cell and records what is there.
+	fn probe(&mut self, dir: Dir) -> Cell {
+		let p = dir.apply(self.pos);
+		let cell = self.step(dir);
+		self.map.set(p, cell);
+		match cell {
+			Cell::Wall => (),
+			Cell::Empty | Cell::System => {
+				if cell == Cell::System {
+					self.sys = Some(p);
+				}
+				self.pos = p;
+			},
+			Cell::Fog => unreachable!(),
+		}
+		cell
+	}
+
+	// Steps into a cell known to be passable.
+	fn go(&mut self, dir: Dir) {
+		let cell = self.step(dir);
+		assert!(cell != Cell::Wall, "bumped into a wall");
+		self.pos = dir.apply(self.pos);
+	}
+
+	fn step(&mut self, dir: Dir) -> Cell {
+		self.steps += 1;
+		self.droid.step(dir)
 	}
 }
 
-#[derive(PartialOrd, Ord, PartialEq, Eq, Clone, Copy, Debug)]
+type Pos = (i64, i64);
+
+#[derive(PartialOrd, Ord, PartialEq, Eq, Hash, Clone, Copy, Debug)]
 enum Dir {
 	N,
 	S,
@@ -182,6 +418,19 @@ impl Dir {
 		}
 	}
 
+	fn turn_left(self) -> Dir {
+		match self {
+			Dir::N => Dir::W,
+			Dir::S => Dir::E,
+			Dir::W => Dir::S,
+			Dir::E => Dir::N,
+		}
+	}
+
+	fn turn_right(self) -> Dir {
+		self.turn_left().rev()
+	}
+
 	fn rev(self) -> Dir {
 		match self {
 			Dir::N => Dir::S,
@@ -473,3 +722,123 @@ fn decode_param(mode: u8, value: isize) -> Param {
 		_ => panic!("unknown parameter mode"),
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const MAZE: &str = "\
+#########
+#D..#...#
+#.#.#.#.#
+#.#...#O#
+#########";
+
+	struct Maze {
+		cells: Vec<Vec<u8>>,
+		pos: Pos,
+	}
+
+	impl Maze {
+		fn from(s: &str) -> Maze {
+			let cells: Vec<Vec<u8>> = s.lines().map(|l| l.bytes().collect()).collect();
+			let pos = find(&cells, b'D');
+			Maze { cells, pos }
+		}
+	}
+
+	impl Droid for Maze {
+		fn step(&mut self, dir: Dir) -> Cell {
+			let p = dir.apply(self.pos);
+			match self.cells[p.1 as usize][p.0 as usize] {
+				b'#' => Cell::Wall,
+				c => {
+					self.pos = p;
+					if c == b'O' { Cell::System } else { Cell::Empty }
+				},
+			}
+		}
+	}
+
+	fn find(cells: &[Vec<u8>], c: u8) -> Pos {
+		for (y, row) in cells.iter().enumerate() {
+			if let Some(x) = row.iter().position(|&x| x == c) {
+				return (x as i64, y as i64);
+			}
+		}
+		panic!("no such cell");
+	}
+
+	#[test]
+	fn strategies() {
+		let mut maps = Vec::new();
+		for &strategy in Strategy::ALL.iter() {
+			let mut drone = Drone::new(Maze::from(MAZE));
+			assert!(drone.explore(strategy) > 0);
+			assert_eq!(drone.sys, Some((6, 2)));
+
+			let flood = flood_fill(&drone.map, drone.sys.unwrap());
+			assert_eq!(flood.steps.get(&(0, 0)), Some(&12));
+			maps.push(drone.map);
+		}
+		assert!(maps.iter().all(|m| *m == maps[0]));
+	}
+
+	#[test]
+	fn route() {
+		let mut drone = Drone::new(Maze::from(MAZE));
+		drone.explore(Strategy::Dfs);
+		let flood = flood_fill(&drone.map, drone.sys.unwrap());
+		let route = flood.route((0, 0)).unwrap();
+		assert_eq!(route.len(), 12);
+
+		let mut maze = Maze::from(MAZE);
+		let cells: Vec<Cell> = route.iter().map(|&dir| maze.step(dir)).collect();
+		assert!(cells[..11].iter().all(|&c| c == Cell::Empty));
+		assert_eq!(cells[11], Cell::System);
+
+		assert_eq!(flood.route(drone.sys.unwrap()), Some(vec![]));
+		assert_eq!(flood.route((-1, -1)), None);
+	}
+
+	#[test]
+	fn frontier_beats_dfs() {
+		let mut dfs = Drone::new(Maze::from(MAZE));
+		let mut frontier = Drone::new(Maze::from(MAZE));
+		assert!(frontier.explore(Strategy::Frontier) <= dfs.explore(Strategy::Dfs));
+	}
+
+	// Winds back and forth through 25 corridors of a 61x51 maze, from
+	// the bottom right corner to the top left one.
+	fn serpentine() -> String {
+		let (w, h) = (61, 51);
+		let mut cells = vec![vec![b'#'; w]; h];
+		for y in (1..h - 1).step_by(2) {
+			for c in cells[y][1..w - 1].iter_mut() {
+				*c = b'.';
+			}
+			if y + 2 < h - 1 {
+				let gap = if y % 4 == 1 { w - 2 } else { 1 };
+				cells[y + 1][gap] = b'.';
+			}
+		}
+		cells[1][1] = b'O';
+		cells[h - 2][w - 2] = b'D';
+		cells.into_iter().map(|row| String::from_utf8(row).unwrap() + "\n").collect()
+	}
+
+	#[test]
+	fn large_maze() {
+		let maze = serpentine();
+		for &strategy in Strategy::ALL.iter() {
+			let mut drone = Drone::new(Maze::from(&maze));
+			drone.explore(strategy);
+			assert_eq!(drone.sys, Some((-58, -48)));
+			assert_eq!(drone.map.bounds(), ((-59, -49), (1, 1)));
+
+			let flood = flood_fill(&drone.map, drone.sys.unwrap());
+			assert_eq!(flood.steps.get(&(0, 0)), Some(&(25 * 58 + 24 * 2)));
+			assert_eq!(flood.route((0, 0)).map(|r| r.len()), Some(25 * 58 + 24 * 2));
+		}
+	}
+}
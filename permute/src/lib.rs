@@ -0,0 +1,383 @@
+use std::ops::Range;
+use std::thread;
+
+// Rearranges `data` into the next lexicographic ordering. Returns false,
+// leaving `data` as is, when it already is the last one.
+pub fn next_permutation<T: Ord>(data: &mut [T]) -> bool {
+	if data.len() < 2 {
+		return false;
+	}
+
+	// Finding the longest non-increasing suffix.
+	let mut i = data.len() - 1;
+	while i > 0 && data[i - 1] >= data[i] {
+		i -= 1;
+	}
+
+	if i == 0 {
+		return false;
+	}
+
+	// data[i - 1] is the pivot, so finding the rightmost
+	// element, which exceeds it.
+	let mut j = data.len() - 1;
+	while data[j] <= data[i - 1] {
+		j -= 1;
+	}
+
+	// data[j] will be the new pivot.
+	debug_assert!(j >= i, "wrong pivot");
+
+	data.swap(i - 1, j);
+	data[i..].reverse();
+
+	true
+}
+
+// The mirror image of `next_permutation`.
+pub fn prev_permutation<T: Ord>(data: &mut [T]) -> bool {
+	if data.len() < 2 {
+		return false;
+	}
+
+	// Finding the longest non-decreasing suffix.
+	let mut i = data.len() - 1;
+	while i > 0 && data[i - 1] <= data[i] {
+		i -= 1;
+	}
+
+	if i == 0 {
+		return false;
+	}
+
+	let mut j = data.len() - 1;
+	while data[j] >= data[i - 1] {
+		j -= 1;
+	}
+
+	data.swap(i - 1, j);
+	data[i..].reverse();
+
+	true
+}
+
+// Lexicographic orderings, starting with the one given. Repeated items
+// give each distinct ordering once.
+pub struct Permutations<T> {
+	next: Option<Vec<T>>,
+	left: Option<u64>,
+}
+
+impl<T: Ord + Clone> Permutations<T> {
+	// Every ordering of `items`.
+	pub fn from(items: &[T]) -> Permutations<T> {
+		let mut first = items.to_vec();
+		first.sort();
+		Permutations::starting(first)
+	}
+
+	// `start` and every ordering after it.
+	pub fn starting(start: Vec<T>) -> Permutations<T> {
+		Permutations { next: Some(start), left: None }
+	}
+
+	// The orderings of distinct `items` numbered by `range`, counting the
+	// sorted one as 0. See `nth_permutation`.
+	pub fn range(items: &[T], range: Range<u64>) -> Permutations<T> {
+		let next = if range.start < range.end {
+			nth_permutation(items, range.start)
+		} else {
+			None
+		};
+		Permutations { next, left: Some(range.end - range.start) }
+	}
+}
+
+impl<T: Ord + Clone> Iterator for Permutations<T> {
+	type Item = Vec<T>;
+
+	fn next(&mut self) -> Option<Vec<T>> {
+		if let Some(left) = self.left.as_mut() {
+			if *left == 0 {
+				return None;
+			}
+			*left -= 1;
+		}
+
+		let current = self.next.take()?;
+		let mut following = current.clone();
+		if next_permutation(&mut following) {
+			self.next = Some(following);
+		}
+		Some(current)
+	}
+}
+
+// Picks of `k` items, keeping their order, in lexicographic order of
+// the positions picked.
+pub struct Combinations<T> {
+	items: Vec<T>,
+	picked: Vec<usize>,
+	done: bool,
+}
+
+impl<T: Clone> Combinations<T> {
+	pub fn from(items: &[T], k: usize) -> Combinations<T> {
+		Combinations {
+			items: items.to_vec(),
+			picked: (0..k).collect(),
+			done: k > items.len(),
+		}
+	}
+}
+
+impl<T: Clone> Iterator for Combinations<T> {
+	type Item = Vec<T>;
+
+	fn next(&mut self) -> Option<Vec<T>> {
+		if self.done {
+			return None;
+		}
+		let current = self.picked.iter().map(|&i| self.items[i].clone()).collect();
+
+		// Bumping the rightmost position which still has room, and
+		// packing everything after it right behind.
+		let (n, k) = (self.items.len(), self.picked.len());
+		match (0..k).rev().find(|&i| self.picked[i] < n - k + i) {
+			Some(i) => {
+				self.picked[i] += 1;
+				for j in i + 1..k {
+					self.picked[j] = self.picked[j - 1] + 1;
+				}
+			},
+			None => self.done = true,
+		}
+
+		Some(current)
+	}
+}
+
+// Heap's algorithm: every ordering of the items, as given, each one a
+// single swap away from the previous one.
+pub struct Heap<T> {
+	data: Vec<T>,
+	counters: Vec<usize>,
+	i: usize,
+	started: bool,
+}
+
+impl<T: Clone> Heap<T> {
+	pub fn from(items: &[T]) -> Heap<T> {
+		Heap {
+			data: items.to_vec(),
+			counters: vec![0; items.len()],
+			i: 1,
+			started: false,
+		}
+	}
+}
+
+impl<T: Clone> Iterator for Heap<T> {
+	type Item = Vec<T>;
+
+	fn next(&mut self) -> Option<Vec<T>> {
+		if !self.started {
+			self.started = true;
+			return Some(self.data.clone());
+		}
+
+		while self.i < self.data.len() {
+			if self.counters[self.i] < self.i {
+				let j = if self.i.is_multiple_of(2) { 0 } else { self.counters[self.i] };
+				self.data.swap(j, self.i);
+				self.counters[self.i] += 1;
+				self.i = 1;
+				return Some(self.data.clone());
+			}
+			self.counters[self.i] = 0;
+			self.i += 1;
+		}
+
+		None
+	}
+}
+
+pub fn factorial(n: usize) -> Option<u64> {
+	(1..=n as u64).try_fold(1u64, |acc, x| acc.checked_mul(x))
+}
+
+// The `index`-th lexicographic ordering of distinct `items`, read off
+// its factorial number system digits.
+pub fn nth_permutation<T: Ord + Clone>(items: &[T], mut index: u64) -> Option<Vec<T>> {
+	if index >= factorial(items.len())? {
+		return None;
+	}
+
+	let mut pool = items.to_vec();
+	pool.sort();
+
+	let mut result = Vec::with_capacity(pool.len());
+	for left in (0..pool.len()).rev() {
+		let block = factorial(left)?;
+		result.push(pool.remove((index / block) as usize));
+		index %= block;
+	}
+	Some(result)
+}
+
+// Cuts `0..total` into at most `parts` contiguous ranges of nearly
+// equal length.
+pub fn split(total: u64, parts: usize) -> Vec<Range<u64>> {
+	let parts = (parts.max(1) as u64).min(total.max(1));
+	let (size, extra) = (total / parts, total % parts);
+
+	let mut start = 0;
+	(0..parts).map(|i| {
+		let end = start + size + if i < extra { 1 } else { 0 };
+		let range = start..end;
+		start = end;
+		range
+	}).collect()
+}
+
+// Runs `f` over every ordering of distinct `items`, each thread taking
+// one index range of the orderings, and collects what the threads
+// return in range order.
+pub fn par_permutations<T, R, F>(items: &[T], threads: usize, f: F) -> Vec<R>
+where
+	T: Ord + Clone + Sync,
+	R: Send,
+	F: Fn(Permutations<T>) -> R + Sync,
+{
+	let total = match factorial(items.len()) {
+		Some(total) => total,
+		None => panic!("{} items have too many orderings", items.len()),
+	};
+
+	thread::scope(|s| {
+		let f = &f;
+		let handles: Vec<_> = split(total, threads)
+			.into_iter()
+			.map(|range| s.spawn(move || f(Permutations::range(items, range))))
+			.collect();
+		handles.into_iter().map(|h| h.join().unwrap()).collect()
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::HashSet;
+
+	// Every ordering of `n` distinct items exactly once.
+	fn assert_all_once(orderings: &[Vec<u8>], n: usize) {
+		let unique: HashSet<_> = orderings.iter().cloned().collect();
+		assert_eq!(orderings.len(), factorial(n).unwrap() as usize);
+		assert_eq!(unique.len(), orderings.len());
+		for o in orderings.iter() {
+			let mut sorted = o.clone();
+			sorted.sort();
+			assert_eq!(sorted, (0..n as u8).collect::<Vec<_>>());
+		}
+	}
+
+	#[test]
+	fn next_and_prev() {
+		let mut data = [0, 1, 2];
+		assert!(next_permutation(&mut data));
+		assert_eq!(data, [0, 2, 1]);
+		assert!(next_permutation(&mut data));
+		assert_eq!(data, [1, 0, 2]);
+		assert!(next_permutation(&mut data));
+		assert_eq!(data, [1, 2, 0]);
+		assert!(next_permutation(&mut data));
+		assert_eq!(data, [2, 0, 1]);
+		assert!(next_permutation(&mut data));
+		assert_eq!(data, [2, 1, 0]);
+		assert!(!next_permutation(&mut data));
+		assert_eq!(data, [2, 1, 0]);
+
+		let mut back = vec![data.to_vec()];
+		while prev_permutation(&mut data) {
+			back.push(data.to_vec());
+		}
+		back.reverse();
+		assert_eq!(back, Permutations::from(&[2, 0, 1]).collect::<Vec<_>>());
+
+		assert!(!next_permutation::<u8>(&mut []));
+		assert!(!prev_permutation(&mut [7]));
+	}
+
+	#[test]
+	fn lexicographic() {
+		let all: Vec<_> = Permutations::from(&[3, 1, 4, 0, 2]).collect();
+		assert_eq!(all[0], [0, 1, 2, 3, 4]);
+		assert_eq!(all[119], [4, 3, 2, 1, 0]);
+		assert!(all.windows(2).all(|w| w[0] < w[1]));
+		assert_all_once(&all, 5);
+
+		for (i, p) in all.iter().enumerate() {
+			assert_eq!(nth_permutation(&[4, 3, 2, 1, 0], i as u64).as_ref(), Some(p));
+		}
+		assert_eq!(nth_permutation(&[0, 1], 2), None);
+
+		let rest: Vec<_> = Permutations::starting(vec![2, 1, 0]).collect();
+		assert_eq!(rest, [[2, 1, 0]]);
+
+		let repeated: Vec<_> = Permutations::from(&[1, 0, 1]).collect();
+		assert_eq!(repeated, [[0, 1, 1], [1, 0, 1], [1, 1, 0]]);
+	}
+
+	#[test]
+	fn heap() {
+		for n in 0..7 {
+			let items: Vec<u8> = (0..n as u8).collect();
+			let all: Vec<_> = Heap::from(&items).collect();
+			assert_all_once(&all, n);
+			assert_eq!(all[0], items);
+
+			for w in all.windows(2) {
+				let moved = w[0].iter().zip(w[1].iter()).filter(|(a, b)| a != b).count();
+				assert_eq!(moved, 2);
+			}
+		}
+	}
+
+	#[test]
+	fn combinations() {
+		let all: Vec<_> = Combinations::from(&['a', 'b', 'c', 'd'], 2).collect();
+		let expected: Vec<Vec<char>> = ["ab", "ac", "ad", "bc", "bd", "cd"]
+			.iter()
+			.map(|s| s.chars().collect())
+			.collect();
+		assert_eq!(all, expected);
+
+		for n in 0..7 {
+			let items: Vec<u8> = (0..n).collect();
+			for k in 0..=n as usize {
+				let all: Vec<_> = Combinations::from(&items, k).collect();
+				let unique: HashSet<_> = all.iter().cloned().collect();
+				let count = factorial(n as usize).unwrap() / factorial(k).unwrap() / factorial(n as usize - k).unwrap();
+				assert_eq!(all.len() as u64, count);
+				assert_eq!(unique.len(), all.len());
+				assert!(all.iter().all(|c| c.len() == k && c.windows(2).all(|w| w[0] < w[1])));
+			}
+		}
+		assert_eq!(Combinations::from(&[0, 1], 3).next(), None);
+	}
+
+	#[test]
+	fn splitting() {
+		assert_eq!(split(10, 3), [0..4, 4..7, 7..10]);
+		assert_eq!(split(2, 4), [0..1, 1..2]);
+		assert_eq!(split(0, 4), vec![0..0]);
+
+		let items: Vec<u8> = (0..6).collect();
+		for &threads in [1, 4, 7, 1000].iter() {
+			let chunks = par_permutations(&items, threads, |p| p.collect::<Vec<_>>());
+			let all: Vec<_> = chunks.into_iter().flatten().collect();
+			assert_all_once(&all, 6);
+			assert!(all.windows(2).all(|w| w[0] < w[1]));
+		}
+	}
+}